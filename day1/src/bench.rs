@@ -0,0 +1,70 @@
+use crate::{
+    calibration::{CalibrationRule, MissingDigits},
+    fast::solution_1_fast,
+    parse_input, solution_1,
+};
+use anyhow::ensure;
+use std::time::Instant;
+
+/// Times `solution_1` against `solution_1_fast` on `megabytes` of generated calibration lines.
+pub fn run(megabytes: usize) -> anyhow::Result<()> {
+    let input = generate(megabytes << 20);
+    println!("generated {} MB", input.len() >> 20);
+
+    let start = Instant::now();
+    let baseline = solution_1(
+        &parse_input(&input[..]),
+        CalibrationRule::default(),
+        MissingDigits::Error,
+    )?;
+    let baseline_time = start.elapsed();
+    let start = Instant::now();
+    let fast = solution_1_fast(&input[..], MissingDigits::Error)?;
+    let fast_time = start.elapsed();
+    ensure!(
+        baseline == fast,
+        "solution_1_fast returned {} but solution_1 returned {}",
+        fast.total,
+        baseline.total
+    );
+
+    for (name, elapsed) in [
+        ("solution_1", baseline_time),
+        ("solution_1_fast", fast_time),
+    ] {
+        let throughput = input.len() as f64 / elapsed.as_secs_f64() / (1 << 20) as f64;
+        println!("{name:<16} {elapsed:>10.2?} {throughput:>10.0} MB/s");
+    }
+    println!(
+        "speedup: {:.1}x",
+        baseline_time.as_secs_f64() / fast_time.as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Lines of 20 to 80 lowercase letters with a few digits mixed in, always at least one.
+fn generate(len: usize) -> Vec<u8> {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut input = Vec::with_capacity(len + 81);
+    while input.len() < len {
+        let line_len = 20 + (next() % 61) as usize;
+        let guaranteed_digit = (next() % line_len as u64) as usize;
+        for i in 0..line_len {
+            let r = next();
+            let byte = if i == guaranteed_digit || r % 16 == 0 {
+                b'0' + (r >> 8) as u8 % 10
+            } else {
+                b'a' + (r >> 8) as u8 % 26
+            };
+            input.push(byte);
+        }
+        input.push(b'\n');
+    }
+    input
+}
@@ -0,0 +1,185 @@
+use anyhow::{bail, Context};
+use std::str::FromStr;
+
+/// What to do with a line that contains no digits at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingDigits {
+    #[default]
+    Error,
+    Skip,
+    Zero,
+}
+
+impl FromStr for MissingDigits {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "skip" => Ok(Self::Skip),
+            "zero" => Ok(Self::Zero),
+            _ => bail!("unknown missing digits policy {s}, expected one of error, skip, zero"),
+        }
+    }
+}
+
+/// How the digits found on a line are turned into its calibration value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalibrationRule {
+    /// The first `first` and last `last` digits concatenated and read in base `radix`.
+    /// Lines with fewer digits use as many as they have, so a lone digit is repeated.
+    Concat {
+        first: usize,
+        last: usize,
+        radix: u32,
+    },
+    /// The sum of every digit on the line.
+    Sum,
+    /// The largest two-digit number made from two digits in line order.
+    LargestPair,
+    /// The smallest two-digit number made from two digits in line order.
+    SmallestPair,
+}
+
+impl Default for CalibrationRule {
+    fn default() -> Self {
+        Self::Concat {
+            first: 1,
+            last: 1,
+            radix: 10,
+        }
+    }
+}
+
+impl CalibrationRule {
+    /// `digits` must not be empty; lines without digits are handled by [`MissingDigits`].
+    pub fn value(&self, digits: &[u32]) -> anyhow::Result<u64> {
+        match *self {
+            Self::Concat { first, last, radix } => {
+                let first = &digits[..first.min(digits.len())];
+                let last = &digits[digits.len() - last.min(digits.len())..];
+                first.iter().chain(last).try_fold(0u64, |acc, &digit| {
+                    if digit >= radix {
+                        bail!("digit {digit} is not valid in base {radix}");
+                    }
+                    acc.checked_mul(radix as u64)
+                        .and_then(|acc| acc.checked_add(digit as u64))
+                        .context("calibration value overflows")
+                })
+            }
+            Self::Sum => Ok(digits.iter().map(|&digit| digit as u64).sum()),
+            Self::LargestPair => Ok(pairs(digits).max().unwrap()),
+            Self::SmallestPair => Ok(pairs(digits).min().unwrap()),
+        }
+    }
+}
+
+fn pairs(digits: &[u32]) -> impl Iterator<Item = u64> + '_ {
+    let pair = |tens: u32, ones: u32| tens as u64 * 10 + ones as u64;
+    let lone_digit = match digits {
+        [digit] => Some(pair(*digit, *digit)),
+        _ => None,
+    };
+    digits
+        .iter()
+        .enumerate()
+        .flat_map(move |(i, &tens)| digits[i + 1..].iter().map(move |&ones| pair(tens, ones)))
+        .chain(lone_digit)
+}
+
+impl FromStr for CalibrationRule {
+    type Err = anyhow::Error;
+
+    /// Accepts `first-last`, `concat:FIRST:LAST[:RADIX]`, `radix:RADIX`, `sum`,
+    /// `largest-pair` and `smallest-pair`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let rule = match parts[..] {
+            ["first-last"] => Self::default(),
+            ["concat", first, last] => Self::Concat {
+                first: first.parse()?,
+                last: last.parse()?,
+                radix: 10,
+            },
+            ["concat", first, last, radix] => Self::Concat {
+                first: first.parse()?,
+                last: last.parse()?,
+                radix: radix.parse()?,
+            },
+            ["radix", radix] => Self::Concat {
+                first: 1,
+                last: 1,
+                radix: radix.parse()?,
+            },
+            ["sum"] => Self::Sum,
+            ["largest-pair"] => Self::LargestPair,
+            ["smallest-pair"] => Self::SmallestPair,
+            _ => bail!("unknown calibration rule {s}"),
+        };
+        if let Self::Concat { first, last, radix } = rule {
+            if !(2..=36).contains(&radix) {
+                bail!("radix must be between 2 and 36, got {radix}");
+            }
+            if first == 0 && last == 0 {
+                bail!("concat needs at least one digit, got concat:0:0");
+            }
+        }
+        Ok(rule)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Calibration {
+    pub total: u64,
+    /// 1-based line numbers that had no digits and were skipped or counted as zero.
+    pub lines_without_digits: Vec<usize>,
+}
+
+impl Calibration {
+    pub fn add_line(
+        &mut self,
+        line_number: usize,
+        line_digits: &[u32],
+        rule: CalibrationRule,
+        policy: MissingDigits,
+    ) -> anyhow::Result<()> {
+        if line_digits.is_empty() {
+            match policy {
+                MissingDigits::Error => bail!("line {line_number} has no digits"),
+                MissingDigits::Skip | MissingDigits::Zero => {
+                    self.lines_without_digits.push(line_number)
+                }
+            }
+            return Ok(());
+        }
+        let value = rule
+            .value(line_digits)
+            .with_context(|| format!("line {line_number}"))?;
+        self.total = self
+            .total
+            .checked_add(value)
+            .context("calibration total overflows")?;
+        Ok(())
+    }
+
+    pub fn warning(&self, policy: MissingDigits) -> Option<String> {
+        if self.lines_without_digits.is_empty() {
+            return None;
+        }
+        let action = match policy {
+            MissingDigits::Error => "rejected",
+            MissingDigits::Skip => "skipped",
+            MissingDigits::Zero => "counted as zero",
+        };
+        let line_numbers = self
+            .lines_without_digits
+            .iter()
+            .map(|line_number| line_number.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(format!(
+            "{} line(s) with no digits {action}: {line_numbers}",
+            self.lines_without_digits.len()
+        ))
+    }
+}
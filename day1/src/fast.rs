@@ -0,0 +1,109 @@
+use crate::calibration::{Calibration, CalibrationRule, MissingDigits};
+use std::io::Read;
+
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Solution 1 over raw bytes: lines are found with `memchr` and digits with a word-at-a-time
+/// scan from each end of the line, so nothing is allocated per line.
+pub fn solution_1_fast(mut input: impl Read, policy: MissingDigits) -> anyhow::Result<Calibration> {
+    let mut calibration = Calibration::default();
+    let mut line_number = 0;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut filled = 0;
+    loop {
+        if filled == buffer.len() {
+            buffer.resize(buffer.len() * 2, 0);
+        }
+        let read = input.read(&mut buffer[filled..])?;
+        if read == 0 {
+            break;
+        }
+        let start = filled;
+        filled += read;
+
+        let mut line_start = 0;
+        for newline in memchr::memchr_iter(b'\n', &buffer[start..filled]) {
+            let line_end = start + newline;
+            line_number += 1;
+            add_line(
+                &mut calibration,
+                line_number,
+                &buffer[line_start..line_end],
+                policy,
+            )?;
+            line_start = line_end + 1;
+        }
+        buffer.copy_within(line_start..filled, 0);
+        filled -= line_start;
+    }
+    if filled > 0 {
+        add_line(&mut calibration, line_number + 1, &buffer[..filled], policy)?;
+    }
+    Ok(calibration)
+}
+
+fn add_line(
+    calibration: &mut Calibration,
+    line_number: usize,
+    line: &[u8],
+    policy: MissingDigits,
+) -> anyhow::Result<()> {
+    let digits = match (first_digit(line), last_digit(line)) {
+        (Some(first), Some(last)) => [first, last],
+        _ => return calibration.add_line(line_number, &[], CalibrationRule::default(), policy),
+    };
+    calibration.add_line(line_number, &digits, CalibrationRule::default(), policy)
+}
+
+const ONES: u64 = u64::from_le_bytes([0x01; 8]);
+const HIGH_BITS: u64 = ONES * 0x80;
+
+/// Sets the high bit of every byte in `word` that is an ASCII digit.
+fn digit_mask(word: u64) -> u64 {
+    // Checks `b'0' - 1 < byte < b'9' + 1` in every byte at once, see "Determine if a word has
+    // a byte between m and n" in Bit Twiddling Hacks. Bytes with their high bit set never match.
+    let low_bits = word & (ONES * 0x7f);
+    let below_upper = ONES * (0x7f + b'9' as u64 + 1) - low_bits;
+    let above_lower = low_bits + ONES * (0x7f - (b'0' as u64 - 1));
+    below_upper & !word & above_lower & HIGH_BITS
+}
+
+fn first_digit(line: &[u8]) -> Option<u32> {
+    let mut chunks = line.chunks_exact(8);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let mask = digit_mask(u64::from_le_bytes(chunk.try_into().unwrap()));
+        if mask != 0 {
+            return Some(digit_value(
+                line[i * 8 + mask.trailing_zeros() as usize / 8],
+            ));
+        }
+    }
+    chunks
+        .remainder()
+        .iter()
+        .find(|b| b.is_ascii_digit())
+        .map(|&b| digit_value(b))
+}
+
+fn last_digit(line: &[u8]) -> Option<u32> {
+    let mut chunks = line.rchunks_exact(8);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let mask = digit_mask(u64::from_le_bytes(chunk.try_into().unwrap()));
+        if mask != 0 {
+            let chunk_start = line.len() - (i + 1) * 8;
+            return Some(digit_value(
+                line[chunk_start + 7 - mask.leading_zeros() as usize / 8],
+            ));
+        }
+    }
+    chunks
+        .remainder()
+        .iter()
+        .rev()
+        .find(|b| b.is_ascii_digit())
+        .map(|&b| digit_value(b))
+}
+
+fn digit_value(b: u8) -> u32 {
+    (b - b'0') as u32
+}
@@ -1,58 +1,220 @@
+use anyhow::{bail, Context};
 use std::io::{stdin, BufRead};
+mod bench;
+mod calibration;
+mod fast;
+mod tokens;
+use calibration::{Calibration, CalibrationRule, MissingDigits};
+use fast::solution_1_fast;
+use tokens::{Token, TokenMatcher, MAX_ROMAN};
 
-fn main() {
-    let input = parse_input(stdin().lock());
-    println!("solution 1: {}", solution_1(&input));
-    println!("solution 2: {}", solution_2(&input));
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "bench").is_some() {
+        let megabytes = match args.next() {
+            Some(megabytes) => megabytes.parse()?,
+            None => 1024,
+        };
+        return bench::run(megabytes);
+    }
+    let options = parse_args(args)?;
+    if options.fast {
+        let rejected: Vec<&str> = [
+            ("--rule", options.rule != CalibrationRule::default()),
+            ("--roman", options.roman_max.is_some()),
+            ("--explain", options.explain),
+            ("--stream", options.stream),
+        ]
+        .into_iter()
+        .filter_map(|(name, given)| given.then_some(name))
+        .collect();
+        if !rejected.is_empty() {
+            bail!(
+                "--fast only computes solution 1 with the first-last rule, so it cannot be \
+                 combined with {}",
+                rejected.join(", ")
+            );
+        }
+        let calibration_1 = solution_1_fast(stdin().lock(), options.missing_digits)?;
+        println!("solution 1: {}", calibration_1.total);
+        if let Some(warning) = calibration_1.warning(options.missing_digits) {
+            eprintln!("warning: solution 1: {warning}");
+        }
+        return Ok(());
+    }
+    if options.stream && options.explain {
+        bail!("--explain cannot be combined with --stream");
+    }
+    let (calibration_1, calibration_2) = if options.stream {
+        solve_streaming(
+            stdin().lock(),
+            options.roman_max,
+            options.rule,
+            options.missing_digits,
+        )?
+    } else {
+        let input = parse_input(stdin().lock());
+        if options.explain {
+            for (line_number, line) in (1..).zip(&input) {
+                println!("line {line_number}: {line}");
+                for (name, matcher) in [
+                    ("solution 1", TokenMatcher::DIGITS),
+                    ("solution 2", solution_2_matcher(options.roman_max)),
+                ] {
+                    println!("  {name}: {}", explain(line, matcher, options.rule));
+                }
+            }
+        }
+        (
+            solution_1(&input, options.rule, options.missing_digits)?,
+            solution_2(
+                &input,
+                options.roman_max,
+                options.rule,
+                options.missing_digits,
+            )?,
+        )
+    };
+    println!("solution 1: {}", calibration_1.total);
+    println!("solution 2: {}", calibration_2.total);
+    for (name, calibration) in [("solution 1", calibration_1), ("solution 2", calibration_2)] {
+        if let Some(warning) = calibration.warning(options.missing_digits) {
+            eprintln!("warning: {name}: {warning}");
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    rule: CalibrationRule,
+    missing_digits: MissingDigits,
+    roman_max: Option<u32>,
+    stream: bool,
+    explain: bool,
+    fast: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--missing-digits" => {
+                options.missing_digits = args
+                    .next()
+                    .context("--missing-digits expects one of error, skip, zero")?
+                    .parse()?;
+            }
+            "--rule" => {
+                options.rule = args
+                    .next()
+                    .context("--rule expects a calibration rule")?
+                    .parse()?;
+            }
+            "--roman" => {
+                let roman_max = args
+                    .next()
+                    .context("--roman expects the largest numeral to recognize")?
+                    .parse()?;
+                if !(1..=MAX_ROMAN).contains(&roman_max) {
+                    bail!("--roman must be between 1 and {MAX_ROMAN}, got {roman_max}");
+                }
+                options.roman_max = Some(roman_max);
+            }
+            "--stream" => options.stream = true,
+            "--explain" => options.explain = true,
+            "--fast" => options.fast = true,
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    Ok(options)
 }
 
 fn parse_input(input: impl BufRead) -> Vec<String> {
     input.lines().map(|line| line.unwrap()).collect()
 }
 
-fn solution_1(input: &[String]) -> u32 {
-    input
-        .iter()
-        .map(|line| {
-            let line_digits: Vec<u32> = line.chars().filter_map(|c| c.to_digit(10)).collect();
-            line_digits.first().unwrap() * 10 + line_digits.last().unwrap()
-        })
-        .sum()
+fn solution_1(
+    input: &[String],
+    rule: CalibrationRule,
+    policy: MissingDigits,
+) -> anyhow::Result<Calibration> {
+    calibrate(input, TokenMatcher::DIGITS, rule, policy)
 }
 
-fn solution_2(input: &[String]) -> u32 {
-    input
-        .iter()
-        .map(|line| {
-            let line_digits: Vec<u32> = iter_substrings(line)
-                .filter_map(|substr| starts_with_digit(substr))
-                .collect();
-            line_digits.first().unwrap() * 10 + line_digits.last().unwrap()
-        })
-        .sum()
+fn solution_2(
+    input: &[String],
+    roman_max: Option<u32>,
+    rule: CalibrationRule,
+    policy: MissingDigits,
+) -> anyhow::Result<Calibration> {
+    calibrate(input, solution_2_matcher(roman_max), rule, policy)
 }
 
-fn iter_substrings(s: &str) -> impl Iterator<Item = &str> {
-    (0..s.len()).map(|offset| &s[offset..])
+fn solution_2_matcher(roman_max: Option<u32>) -> TokenMatcher {
+    TokenMatcher {
+        words: true,
+        roman_max,
+    }
 }
 
-fn starts_with_digit(s: &str) -> Option<u32> {
-    let matched = match s {
-        s if s.starts_with("one") => Some(1),
-        s if s.starts_with("two") => Some(2),
-        s if s.starts_with("three") => Some(3),
-        s if s.starts_with("four") => Some(4),
-        s if s.starts_with("five") => Some(5),
-        s if s.starts_with("six") => Some(6),
-        s if s.starts_with("seven") => Some(7),
-        s if s.starts_with("eight") => Some(8),
-        s if s.starts_with("nine") => Some(9),
-        _ => None,
-    };
-    if matched.is_some() {
-        return matched;
+/// Computes both solutions in a single pass without holding more than one line in memory.
+fn solve_streaming(
+    mut input: impl BufRead,
+    roman_max: Option<u32>,
+    rule: CalibrationRule,
+    policy: MissingDigits,
+) -> anyhow::Result<(Calibration, Calibration)> {
+    let matcher_2 = solution_2_matcher(roman_max);
+    let mut calibration_1 = Calibration::default();
+    let mut calibration_2 = Calibration::default();
+    let mut buffer = String::new();
+    let mut line_number = 0;
+    while input.read_line(&mut buffer)? != 0 {
+        line_number += 1;
+        let line = buffer.trim_end_matches('\n').trim_end_matches('\r');
+        calibration_1.add_line(
+            line_number,
+            &TokenMatcher::DIGITS.digits(line),
+            rule,
+            policy,
+        )?;
+        calibration_2.add_line(line_number, &matcher_2.digits(line), rule, policy)?;
+        buffer.clear();
+    }
+    Ok((calibration_1, calibration_2))
+}
+
+fn calibrate(
+    input: &[String],
+    matcher: TokenMatcher,
+    rule: CalibrationRule,
+    policy: MissingDigits,
+) -> anyhow::Result<Calibration> {
+    let mut calibration = Calibration::default();
+    for (line_number, line) in (1..).zip(input) {
+        calibration.add_line(line_number, &matcher.digits(line), rule, policy)?;
+    }
+    Ok(calibration)
+}
+
+/// Describes which tokens were matched on a line and the value they produce.
+fn explain(line: &str, matcher: TokenMatcher, rule: CalibrationRule) -> String {
+    let tokens = matcher.tokens(line);
+    if tokens.is_empty() {
+        return "no digits".to_string();
     }
-    s.chars().next().and_then(|c| c.to_digit(10))
+    let digits: Vec<u32> = tokens.iter().flat_map(Token::digits).collect();
+    let value = match rule.value(&digits) {
+        Ok(value) => value.to_string(),
+        Err(err) => format!("error: {err}"),
+    };
+    let tokens = tokens
+        .iter()
+        .map(|token| format!("{}@{}={}", token.text, token.offset, token.value))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{tokens} -> {value}")
 }
 
 #[cfg(test)]
@@ -60,16 +222,223 @@ mod tests {
     use super::*;
     const EXAMPLE: &str = include_str!("../example.txt");
     const EXAMPLE2: &str = include_str!("../example2.txt");
+    const MISSING_DIGITS: &str = "1abc2\nabc\npqr3stu8vwx\n\n";
 
     #[test]
     fn test_solution_1_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(solution_1(&input), 142);
+        assert_eq!(
+            solution_1(&input, CalibrationRule::default(), MissingDigits::Error)
+                .unwrap()
+                .total,
+            142
+        );
     }
 
     #[test]
     fn test_solution_2_example() {
         let input = parse_input(EXAMPLE2.as_bytes());
-        assert_eq!(solution_2(&input), 281);
+        assert_eq!(
+            solution_2(
+                &input,
+                None,
+                CalibrationRule::default(),
+                MissingDigits::Error
+            )
+            .unwrap()
+            .total,
+            281
+        );
+    }
+
+    #[test]
+    fn test_missing_digits_error() {
+        let input = parse_input(MISSING_DIGITS.as_bytes());
+        let err = solution_1(&input, CalibrationRule::default(), MissingDigits::Error).unwrap_err();
+        assert_eq!(err.to_string(), "line 2 has no digits");
+        let err = solution_2(
+            &input,
+            None,
+            CalibrationRule::default(),
+            MissingDigits::Error,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "line 2 has no digits");
+    }
+
+    #[test]
+    fn test_missing_digits_skip_and_zero() {
+        let input = parse_input(MISSING_DIGITS.as_bytes());
+        for policy in [MissingDigits::Skip, MissingDigits::Zero] {
+            let expected = Calibration {
+                total: 50,
+                lines_without_digits: vec![2, 4],
+            };
+            let rule = CalibrationRule::default();
+            assert_eq!(solution_1(&input, rule, policy).unwrap(), expected);
+            assert_eq!(solution_2(&input, None, rule, policy).unwrap(), expected);
+        }
+        let calibration = solution_2(
+            &input,
+            None,
+            CalibrationRule::default(),
+            MissingDigits::Skip,
+        )
+        .unwrap();
+        assert_eq!(
+            calibration.warning(MissingDigits::Skip).unwrap(),
+            "2 line(s) with no digits skipped: 2, 4"
+        );
+    }
+
+    #[test]
+    fn test_calibration_rules_example() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        for (rule, expected) in [
+            ("first-last", 142),
+            ("concat:2:2", 1212 + 3838 + 1245 + 77),
+            ("concat:1:3:10", 112 + 338 + 1345 + 77),
+            ("radix:16", 0x12 + 0x38 + 0x15 + 0x77),
+            ("sum", 3 + 11 + 15 + 7),
+            ("largest-pair", 12 + 38 + 45 + 77),
+            ("smallest-pair", 12 + 38 + 12 + 77),
+        ] {
+            let rule = rule.parse().unwrap();
+            let calibration = solution_1(&input, rule, MissingDigits::Error).unwrap();
+            assert_eq!(calibration.total, expected, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn test_calibration_rule_radix_rejects_digits() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let rule = "radix:8".parse().unwrap();
+        let err = solution_1(&input, rule, MissingDigits::Error).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: digit 8 is not valid in base 8");
+        assert!("radix:1".parse::<CalibrationRule>().is_err());
+        assert!("concat:1".parse::<CalibrationRule>().is_err());
+        assert!("concat:0:0".parse::<CalibrationRule>().is_err());
+        assert!("concat:0:1".parse::<CalibrationRule>().is_ok());
+    }
+
+    #[test]
+    fn test_solve_streaming_matches_in_memory() {
+        for (example, policy) in [
+            (EXAMPLE, MissingDigits::Error),
+            (EXAMPLE2, MissingDigits::Skip),
+            (MISSING_DIGITS, MissingDigits::Zero),
+        ] {
+            let input = parse_input(example.as_bytes());
+            assert_eq!(
+                solve_streaming(example.as_bytes(), None, CalibrationRule::default(), policy)
+                    .unwrap(),
+                (
+                    solution_1(&input, CalibrationRule::default(), policy).unwrap(),
+                    solution_2(&input, None, CalibrationRule::default(), policy).unwrap()
+                )
+            );
+        }
+        let crlf = "two1nine\r\n7pqrstsixteen\r\n";
+        let (_, calibration_2) = solve_streaming(
+            crlf.as_bytes(),
+            None,
+            CalibrationRule::default(),
+            MissingDigits::Error,
+        )
+        .unwrap();
+        assert_eq!(calibration_2.total, 29 + 76);
+    }
+
+    #[test]
+    fn test_roman_tokens() {
+        let roman = |roman_max| TokenMatcher {
+            words: true,
+            roman_max: Some(roman_max),
+        };
+        for (line, roman_max, expected) in [
+            ("vii", 10, vec![7]),
+            ("vi", 10, vec![6]),
+            ("viiii", 10, vec![8, 1]),
+            ("xvii", 9, vec![7]),
+            ("xvii", 20, vec![1, 7]),
+            ("MMMDCCCLXXXVIII", 3999, vec![3, 8, 8, 8]),
+            ("iv2IX", 9, vec![4, 2, 9]),
+            ("fivesixnine", 9, vec![5, 6, 9]),
+        ] {
+            assert_eq!(roman(roman_max).digits(line), expected, "{line}");
+        }
+        assert_eq!(TokenMatcher::DIGITS.digits("iv2ix"), vec![2]);
+
+        let input = parse_input("abcvii\nix3\nsixiv\n".as_bytes());
+        let calibration = solution_2(
+            &input,
+            Some(9),
+            CalibrationRule::default(),
+            MissingDigits::Error,
+        )
+        .unwrap();
+        assert_eq!(calibration.total, 77 + 93 + 64);
+
+        // Numerals worth 10 or more take part in every rule through their decimal digits.
+        let line = ["abxvii3".to_string()];
+        let value = |rule: &str| {
+            solution_2(&line, Some(20), rule.parse().unwrap(), MissingDigits::Error)
+                .unwrap()
+                .total
+        };
+        assert_eq!(value("first-last"), 13);
+        assert_eq!(value("concat:2:1"), 173);
+        assert_eq!(value("sum"), 1 + 7 + 3);
+        assert_eq!(value("largest-pair"), 73);
+        assert_eq!(value("smallest-pair"), 13);
+        assert_eq!(
+            explain(
+                &line[0],
+                solution_2_matcher(Some(20)),
+                CalibrationRule::default()
+            ),
+            "xvii@2=17 3@6=3 -> 13"
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let matcher = TokenMatcher {
+            words: true,
+            roman_max: Some(9),
+        };
+        assert_eq!(
+            explain("xtwone3iv", matcher, CalibrationRule::default()),
+            "two@1=2 one@3=1 3@6=3 iv@7=4 -> 24"
+        );
+        assert_eq!(
+            explain("abc", TokenMatcher::DIGITS, CalibrationRule::default()),
+            "no digits"
+        );
+    }
+
+    #[test]
+    fn test_solution_1_fast_matches_solution_1() {
+        for (example, policy) in [
+            (EXAMPLE, MissingDigits::Error),
+            (EXAMPLE2, MissingDigits::Skip),
+            (MISSING_DIGITS, MissingDigits::Zero),
+        ] {
+            let input = parse_input(example.as_bytes());
+            assert_eq!(
+                solution_1_fast(example.as_bytes(), policy).unwrap(),
+                solution_1(&input, CalibrationRule::default(), policy).unwrap()
+            );
+        }
+        let long_lines = "abcdefghij1klmnopqrstuvwxyz2abcdefgh\r\nabcdefghijklmnop3\n\u{e9}9\u{e9}";
+        let input = parse_input(long_lines.as_bytes());
+        assert_eq!(
+            solution_1_fast(long_lines.as_bytes(), MissingDigits::Error)
+                .unwrap()
+                .total,
+            solution_1(&input, CalibrationRule::default(), MissingDigits::Error)
+                .unwrap()
+                .total
+        );
     }
 }
@@ -0,0 +1,180 @@
+use std::{cmp::max, collections::HashMap, sync::OnceLock};
+
+/// Which kinds of tokens count as digits on a calibration line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TokenMatcher {
+    /// Spelled out digits, `one` through `nine`.
+    pub words: bool,
+    /// Roman numerals up to and including this value, e.g. `iv` or `vii`.
+    pub roman_max: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub offset: usize,
+    pub text: &'a str,
+    pub value: u32,
+}
+
+impl Token<'_> {
+    pub fn digits(&self) -> Vec<u32> {
+        self.value
+            .to_string()
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .collect()
+    }
+}
+
+const WORDS: [(&str, u32); 9] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+pub const MAX_ROMAN: u32 = 3999;
+
+impl TokenMatcher {
+    pub const DIGITS: Self = Self {
+        words: false,
+        roman_max: None,
+    };
+
+    /// Finds every token on the line in order. Words may overlap each other (`twone` is 2
+    /// then 1), but a roman numeral always takes the longest match at its offset and no
+    /// other numeral starts inside it or inside a word, so `vii` is only 7 and `five` is
+    /// never read as `iv`.
+    pub fn tokens<'a>(&self, line: &'a str) -> Vec<Token<'a>> {
+        let mut tokens = Vec::new();
+        let mut roman_blocked_until = 0;
+        for (offset, c) in line.char_indices() {
+            let rest = &line[offset..];
+            let token = |len: usize, value: u32| Token {
+                offset,
+                text: &rest[..len],
+                value,
+            };
+            if self.words {
+                if let Some((word, value)) = starts_with_word(rest) {
+                    tokens.push(token(word.len(), value));
+                    roman_blocked_until = max(roman_blocked_until, offset + word.len());
+                    continue;
+                }
+            }
+            if let Some(roman_max) = self.roman_max.filter(|_| offset >= roman_blocked_until) {
+                if let Some((len, value)) = starts_with_roman(rest, roman_max) {
+                    tokens.push(token(len, value));
+                    roman_blocked_until = offset + len;
+                    continue;
+                }
+            }
+            if let Some(value) = c.to_digit(10) {
+                tokens.push(token(1, value));
+            }
+        }
+        tokens
+    }
+
+    /// The digits of every token in order. A roman numeral worth 10 or more gives each of its
+    /// decimal digits, so `xvii` reads as 1 then 7.
+    pub fn digits(&self, line: &str) -> Vec<u32> {
+        self.tokens(line).iter().flat_map(Token::digits).collect()
+    }
+}
+
+fn starts_with_word(s: &str) -> Option<(&'static str, u32)> {
+    WORDS.into_iter().find(|(word, _)| s.starts_with(word))
+}
+
+/// Returns the length and value of the longest canonical roman numeral at the start of `s`
+/// whose value is at most `roman_max`.
+fn starts_with_roman(s: &str, roman_max: u32) -> Option<(usize, u32)> {
+    let numerals = roman_numerals();
+    let longest = numerals.longest[roman_max as usize];
+    let run = s
+        .chars()
+        .take(longest)
+        .take_while(|&c| roman_digit(c).is_some())
+        .count();
+    let mut lowercase = [0u8; LONGEST_ROMAN];
+    for (byte, c) in lowercase.iter_mut().zip(s.bytes().take(run)) {
+        *byte = c.to_ascii_lowercase();
+    }
+    (1..=run).rev().find_map(|len| {
+        let candidate = std::str::from_utf8(&lowercase[..len]).ok()?;
+        let value = *numerals.values.get(candidate)?;
+        (value <= roman_max).then_some((len, value))
+    })
+}
+
+/// The length of `mmmdccclxxxviii`, the longest numeral up to [`MAX_ROMAN`].
+const LONGEST_ROMAN: usize = 15;
+
+struct RomanNumerals {
+    /// Every canonical lowercase numeral up to [`MAX_ROMAN`] and its value.
+    values: HashMap<String, u32>,
+    /// The length of the longest numeral worth at most each value.
+    longest: Vec<usize>,
+}
+
+fn roman_numerals() -> &'static RomanNumerals {
+    static NUMERALS: OnceLock<RomanNumerals> = OnceLock::new();
+    NUMERALS.get_or_init(|| {
+        let mut numerals = RomanNumerals {
+            values: HashMap::new(),
+            longest: vec![0],
+        };
+        for value in 1..=MAX_ROMAN {
+            let numeral = to_roman(value);
+            let longest = numerals.longest[value as usize - 1].max(numeral.len());
+            numerals.longest.push(longest);
+            numerals.values.insert(numeral, value);
+        }
+        numerals
+    })
+}
+
+fn roman_digit(c: char) -> Option<u32> {
+    match c.to_ascii_lowercase() {
+        'i' => Some(1),
+        'v' => Some(5),
+        'x' => Some(10),
+        'l' => Some(50),
+        'c' => Some(100),
+        'd' => Some(500),
+        'm' => Some(1000),
+        _ => None,
+    }
+}
+
+fn to_roman(mut value: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut roman = String::new();
+    for (numeral_value, numeral) in NUMERALS {
+        while value >= numeral_value {
+            roman.push_str(numeral);
+            value -= numeral_value;
+        }
+    }
+    roman
+}
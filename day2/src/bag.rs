@@ -0,0 +1,82 @@
+use crate::color::{Colors, Cubes};
+use anyhow::{bail, Context};
+use std::{fmt, fs, path::Path, str::FromStr};
+
+/// A bag described by color name, written as `red=12,green=13,blue=14`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagSpec {
+    pub cubes: Vec<(String, u32)>,
+}
+
+impl BagSpec {
+    /// Colors the games never mention are dropped since no reveal can ask for them.
+    pub fn resolve(&self, colors: &Colors) -> Cubes {
+        self.cubes
+            .iter()
+            .filter_map(|(color, number)| Some((colors.get(color)?, *number)))
+            .collect()
+    }
+
+    /// Every color of the bag, interning the ones no game mentions, for when cubes the
+    /// games never reveal still matter.
+    pub fn resolve_all(&self, colors: &mut Colors) -> Cubes {
+        self.cubes
+            .iter()
+            .map(|(color, number)| (colors.intern(color), *number))
+            .collect()
+    }
+}
+
+impl FromStr for BagSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cubes: Vec<(String, u32)> = Vec::new();
+        for entry in s.split(',') {
+            let (color, number) = entry
+                .trim()
+                .split_once('=')
+                .with_context(|| format!("expected color=number in bag, got {entry:?}"))?;
+            let color = color.trim();
+            if color.is_empty() {
+                bail!("missing color name in bag entry {entry:?}");
+            }
+            if cubes.iter().any(|(existing, _)| existing == color) {
+                bail!("color {color} appears more than once in bag {s:?}");
+            }
+            let number = number
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of {color} cubes in bag {s:?}"))?;
+            cubes.push((color.to_string(), number));
+        }
+        Ok(BagSpec { cubes })
+    }
+}
+
+impl fmt::Display for BagSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (color, number)) in self.cubes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{color}={number}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads one bag per line, ignoring blank lines and lines starting with `#`.
+pub fn read_bags(path: &Path) -> anyhow::Result<Vec<BagSpec>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("reading bags from {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            line.parse()
+                .with_context(|| format!("{}:{}", path.display(), i + 1))
+        })
+        .collect()
+}
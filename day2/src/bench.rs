@@ -0,0 +1,123 @@
+use crate::{parse_input, part_1, part_2, BagSpec, PART_1_BAG};
+use anyhow::{ensure, Context};
+use std::{collections::HashMap, time::Instant};
+
+const COLOR_NAMES: [&str; 6] = ["red", "green", "blue", "yellow", "purple", "orange"];
+
+/// Times parsing and both parts with dense per-color reveals against hash map reveals on
+/// `games` generated games.
+pub fn run(games: usize) -> anyhow::Result<()> {
+    let input = generate(games);
+    println!("generated {games} games, {} MB", input.len() >> 20);
+
+    let start = Instant::now();
+    let games = parse_hashed(&input)?;
+    let baseline = (part_1_hashed(&games)?, part_2_hashed(&games));
+    let baseline_time = start.elapsed();
+    let start = Instant::now();
+    let games = parse_input(input.as_bytes()).map_err(|errors| anyhow::anyhow!("{}", errors[0]))?;
+    let dense = (part_1(&games), part_2(&games));
+    let dense_time = start.elapsed();
+    ensure!(
+        baseline == dense,
+        "dense reveals gave {dense:?} but hash map reveals gave {baseline:?}"
+    );
+
+    println!("hash map {baseline_time:>10.2?}");
+    println!("dense    {dense_time:>10.2?}");
+    println!(
+        "speedup: {:.1}x",
+        baseline_time.as_secs_f64() / dense_time.as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Games with 1 to 10 reveals of 1 to 4 distinct colors, 1 to 20 cubes each.
+fn generate(games: usize) -> String {
+    let mut state = 0x5851_f42d_4c95_7f2d_u64;
+    let mut next = move |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % bound
+    };
+    let mut input = String::new();
+    for id in 1..=games {
+        input.push_str(&format!("Game {id}:"));
+        for reveal in 0..1 + next(10) {
+            if reveal > 0 {
+                input.push(';');
+            }
+            let first = next(COLOR_NAMES.len() as u64) as usize;
+            for i in 0..1 + next(4) as usize {
+                if i > 0 {
+                    input.push(',');
+                }
+                let color = COLOR_NAMES[(first + i) % COLOR_NAMES.len()];
+                input.push_str(&format!(" {} {color}", 1 + next(20)));
+            }
+        }
+        input.push('\n');
+    }
+    input
+}
+
+type HashedGame = (u32, Vec<HashMap<String, u32>>);
+
+/// The reveal representation the dense one replaced, kept as the benchmark baseline.
+fn parse_hashed(input: &str) -> anyhow::Result<Vec<HashedGame>> {
+    input
+        .lines()
+        .map(|line| {
+            let (game, reveals) = line.split_once(':').context("missing ':'")?;
+            let id = game
+                .strip_prefix("Game ")
+                .context("missing \"Game \"")?
+                .parse()?;
+            let reveals = reveals
+                .split(';')
+                .map(|reveal| {
+                    reveal
+                        .split(',')
+                        .map(|cubes| {
+                            let (number, color) =
+                                cubes.trim().split_once(' ').context("missing ' '")?;
+                            Ok((color.to_string(), number.parse()?))
+                        })
+                        .collect()
+                })
+                .collect::<anyhow::Result<_>>()?;
+            Ok((id, reveals))
+        })
+        .collect()
+}
+
+fn part_1_hashed(games: &[HashedGame]) -> anyhow::Result<u32> {
+    let bag: HashMap<String, u32> = PART_1_BAG.parse::<BagSpec>()?.cubes.into_iter().collect();
+    Ok(games
+        .iter()
+        .filter(|(_, reveals)| {
+            reveals
+                .iter()
+                .flatten()
+                .all(|(color, number)| number <= bag.get(color).unwrap_or(&0))
+        })
+        .map(|(id, _)| id)
+        .sum())
+}
+
+fn part_2_hashed(games: &[HashedGame]) -> u64 {
+    games
+        .iter()
+        .map(|(_, reveals)| {
+            let mut bag: HashMap<&str, u64> = HashMap::new();
+            for (color, number) in reveals.iter().flatten() {
+                let current_max = bag.entry(color).or_insert(*number as u64);
+                if *current_max < *number as u64 {
+                    *current_max = *number as u64;
+                }
+            }
+            bag.values().product::<u64>()
+        })
+        .sum()
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ColorId(u16);
+
+/// Interns color names so games can refer to any color by a small id.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Colors {
+    names: Vec<String>,
+    ids: HashMap<String, ColorId>,
+}
+
+impl Colors {
+    pub fn intern(&mut self, name: &str) -> ColorId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = ColorId(u16::try_from(self.names.len()).expect("too many colors"));
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<ColorId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: ColorId) -> &str {
+        &self.names[id.0 as usize]
+    }
+
+    /// Every interned color, in id order.
+    pub fn iter(&self) -> impl Iterator<Item = (ColorId, &str)> + '_ {
+        (0..)
+            .zip(&self.names)
+            .map(|(id, name)| (ColorId(id), name.as_str()))
+    }
+}
+
+/// Cube counts indexed by color id, used for both reveals and bags. A color can be present
+/// with zero cubes, as in `0 red`, which is kept apart from a color that is not listed at all.
+/// Colors past the end of the array are not listed, so arrays of different lengths compare
+/// equal when they only differ in trailing unlisted colors.
+#[derive(Debug, Clone, Default)]
+pub struct Cubes(Vec<Option<u32>>);
+
+impl Cubes {
+    /// The number of cubes of `color`, zero when it is not listed.
+    pub fn get(&self, color: ColorId) -> u32 {
+        self.listed(color.0 as usize).unwrap_or(0)
+    }
+
+    /// Whether `color` is listed, even with zero cubes.
+    pub fn contains(&self, color: ColorId) -> bool {
+        self.listed(color.0 as usize).is_some()
+    }
+
+    pub fn set(&mut self, color: ColorId, number: u32) {
+        let index = color.0 as usize;
+        if index >= self.0.len() {
+            self.0.resize(index + 1, None);
+        }
+        self.0[index] = Some(number);
+    }
+
+    /// Listed colors, including those with zero cubes, in color id order.
+    pub fn iter(&self) -> impl Iterator<Item = (ColorId, u32)> + '_ {
+        (0..)
+            .zip(&self.0)
+            .filter_map(|(id, number)| Some((ColorId(id), (*number)?)))
+    }
+
+    /// Whether every count in `self` is at most the count of the same color in `other`.
+    pub fn fits_in(&self, other: &Cubes) -> bool {
+        self.iter()
+            .all(|(color, number)| number <= other.get(color))
+    }
+
+    /// Raises every count in `self` to at least the count of the same color in `other`,
+    /// listing the colors `other` lists.
+    pub fn max_assign(&mut self, other: &Cubes) {
+        if other.0.len() > self.0.len() {
+            self.0.resize(other.0.len(), None);
+        }
+        for (number, other) in self.0.iter_mut().zip(&other.0) {
+            *number = (*number).max(*other);
+        }
+    }
+
+    fn listed(&self, index: usize) -> Option<u32> {
+        self.0.get(index).copied().flatten()
+    }
+}
+
+impl PartialEq for Cubes {
+    fn eq(&self, other: &Self) -> bool {
+        (0..self.0.len().max(other.0.len())).all(|i| self.listed(i) == other.listed(i))
+    }
+}
+
+impl Eq for Cubes {}
+
+impl<const N: usize> From<[(ColorId, u32); N]> for Cubes {
+    fn from(cubes: [(ColorId, u32); N]) -> Self {
+        cubes.into_iter().collect()
+    }
+}
+
+impl FromIterator<(ColorId, u32)> for Cubes {
+    fn from_iter<T: IntoIterator<Item = (ColorId, u32)>>(iter: T) -> Self {
+        let mut cubes = Cubes::default();
+        for (color, number) in iter {
+            cubes.set(color, number);
+        }
+        cubes
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{color::Cubes, Game};
+use anyhow::bail;
+use std::{f64::consts::TAU, str::FromStr};
+
+/// How the cubes of a single reveal are drawn from the bag. Cubes are always put back
+/// between reveals, so reveals are independent of each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawModel {
+    /// Each cube is put back before the next one is drawn (multinomial).
+    WithReplacement,
+    /// All cubes of a reveal are taken out together (multivariate hypergeometric).
+    WithoutReplacement,
+}
+
+impl FromStr for DrawModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "with-replacement" => Ok(Self::WithReplacement),
+            "without-replacement" => Ok(Self::WithoutReplacement),
+            _ => bail!("unknown draw model {s}, expected with-replacement or without-replacement"),
+        }
+    }
+}
+
+/// Natural log of the probability of seeing exactly this reveal when drawing from `bag`,
+/// or negative infinity if the reveal is impossible.
+pub fn reveal_log_likelihood(bag: &Cubes, reveal: &Cubes, model: DrawModel) -> f64 {
+    let bag_total: u64 = bag.iter().map(|(_, number)| u64::from(number)).sum();
+    let drawn: u64 = reveal.iter().map(|(_, number)| u64::from(number)).sum();
+    match model {
+        DrawModel::WithoutReplacement => {
+            if !reveal.fits_in(bag) {
+                return f64::NEG_INFINITY;
+            }
+            let ways: f64 = reveal
+                .iter()
+                .map(|(color, number)| ln_choose(bag.get(color).into(), number.into()))
+                .sum();
+            ways - ln_choose(bag_total, drawn)
+        }
+        DrawModel::WithReplacement => {
+            if reveal
+                .iter()
+                .any(|(color, number)| number > 0 && bag.get(color) == 0)
+            {
+                return f64::NEG_INFINITY;
+            }
+            let orderings = ln_factorial(drawn)
+                - reveal
+                    .iter()
+                    .map(|(_, number)| ln_factorial(number.into()))
+                    .sum::<f64>();
+            let draws: f64 = reveal
+                .iter()
+                .map(|(color, number)| {
+                    number as f64 * (bag.get(color) as f64 / bag_total as f64).ln()
+                })
+                .sum();
+            orderings + draws
+        }
+    }
+}
+
+/// Natural log of the probability of the game's whole reveal sequence.
+pub fn game_log_likelihood(bag: &Cubes, game: &Game, model: DrawModel) -> f64 {
+    game.reveals
+        .iter()
+        .map(|reveal| reveal_log_likelihood(bag, reveal, model))
+        .sum()
+}
+
+/// Indices into `bags` with their log-likelihood for `game`, most likely first.
+pub fn rank_bags(bags: &[Cubes], game: &Game, model: DrawModel) -> Vec<(usize, f64)> {
+    let mut ranked: Vec<(usize, f64)> = bags
+        .iter()
+        .map(|bag| game_log_likelihood(bag, game, model))
+        .enumerate()
+        .collect();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    ranked
+}
+
+/// From here on Stirling's series is as precise as an f64, so counts the size of a bag of a
+/// billion cubes cost no more than small ones.
+const STIRLING_FROM: u64 = 256;
+
+fn ln_factorial(n: u64) -> f64 {
+    if n < STIRLING_FROM {
+        return (2..=n).map(|i| (i as f64).ln()).sum();
+    }
+    let n = n as f64;
+    n * n.ln() - n + 0.5 * (TAU * n).ln() + 1.0 / (12.0 * n) - 1.0 / (360.0 * n.powi(3))
+}
+
+/// Below this many factors `ln_choose` sums them directly, which is still quick and avoids
+/// subtracting large factorials that have lost the precision a near-certain draw needs.
+const DIRECT_CHOOSE_UP_TO: u64 = 1 << 16;
+
+fn ln_choose(n: u64, k: u64) -> f64 {
+    let k = k.min(n - k);
+    if k < DIRECT_CHOOSE_UP_TO {
+        return (1..=k).map(|i| ((n - k + i) as f64 / i as f64).ln()).sum();
+    }
+    ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+}
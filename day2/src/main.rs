@@ -1,96 +1,403 @@
+use anyhow::{bail, Context};
 use std::{
     collections::HashMap,
     io::{stdin, BufRead},
+    path::Path,
 };
+mod bag;
+mod bench;
+mod color;
+mod likelihood;
+mod min_bag;
+mod query;
+mod record;
+mod stats;
+use bag::{read_bags, BagSpec};
+use color::{ColorId, Colors, Cubes};
+use likelihood::{rank_bags, DrawModel};
+use min_bag::{minimal_bag, BagSize};
+use query::Query;
+use record::{GameDisplay, ValidationError};
+use stats::{stats, StatsFormat};
 
-fn main() {
-    let games = parse_input(stdin().lock());
-    println!("part 1: {}", part_1(&games));
-    println!("part 1: {}", part_2(&games));
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "bench").is_some() {
+        let games = match args.next() {
+            Some(games) => games.parse()?,
+            None => 1_000_000,
+        };
+        return bench::run(games);
+    }
+    let options = parse_args(args)?;
+    let games = match parse_input(stdin().lock()) {
+        Ok(games) => games,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{error}");
+            }
+            bail!("found {} problem(s) in the game records", errors.len());
+        }
+    };
+    if options.format {
+        for game in &games.games {
+            println!("{}", game.display(&games.colors));
+        }
+        return Ok(());
+    }
+    if let Some(format) = options.stats {
+        let stats = stats(&games.games, &games.colors);
+        match format {
+            StatsFormat::Table => print!("{stats}"),
+            StatsFormat::Json => println!("{}", stats.json()),
+        }
+        return Ok(());
+    }
+    if let Some(model) = options.likelihood {
+        if options.bags.is_empty() {
+            bail!("--likelihood needs at least one --bag or --bags to rank");
+        }
+        print_likelihoods(&games, &options.bags, model);
+        return Ok(());
+    }
+    if let Some(query) = &options.filter {
+        let matching = filter_games(&games, query);
+        let ids: Vec<u32> = matching.iter().map(|game| game.id).collect();
+        println!("matching games: [{}]", join_ids(&ids));
+        println!(
+            "count: {}, sum of ids: {}, sum of powers: {}",
+            matching.len(),
+            ids.iter().sum::<u32>(),
+            matching
+                .iter()
+                .map(|game| power(&smallest_possible_bag(game)))
+                .sum::<u64>()
+        );
+        return Ok(());
+    }
+    if let Some(size) = options.min_bag {
+        let at_least = options.at_least.unwrap_or(games.games.len());
+        print_minimal_bag(&games, at_least, size)?;
+        return Ok(());
+    }
+    if options.bags.is_empty() {
+        println!("part 1: {}", part_1(&games));
+        println!("part 1: {}", part_2(&games));
+    }
+    for bag in &options.bags {
+        let ids = possible_game_ids(&games, bag);
+        let sum: u32 = ids.iter().sum();
+        println!("bag {bag}: possible games [{}], sum {sum}", join_ids(&ids));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    bags: Vec<BagSpec>,
+    likelihood: Option<DrawModel>,
+    min_bag: Option<BagSize>,
+    at_least: Option<usize>,
+    filter: Option<Query>,
+    format: bool,
+    stats: Option<StatsFormat>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bag" => {
+                let bag = args
+                    .next()
+                    .context("--bag expects a bag like red=12,green=13,blue=14")?;
+                options.bags.push(bag.parse()?);
+            }
+            "--bags" => {
+                let path = args.next().context("--bags expects a file of bags")?;
+                options.bags.extend(read_bags(Path::new(&path))?);
+            }
+            "--likelihood" => {
+                let model = args
+                    .next()
+                    .context("--likelihood expects with-replacement or without-replacement")?;
+                options.likelihood = Some(model.parse()?);
+            }
+            "--min-bag" => {
+                let size = args.next().context("--min-bag expects total or product")?;
+                options.min_bag = Some(size.parse()?);
+            }
+            "--at-least" => {
+                let at_least = args
+                    .next()
+                    .context("--at-least expects a number of games")?;
+                options.at_least = Some(at_least.parse()?);
+            }
+            "--filter" => {
+                let query = args.next().context("--filter expects a query")?;
+                options.filter = Some(query.parse()?);
+            }
+            "--format" => options.format = true,
+            "--stats" => {
+                let format = args.next().context("--stats expects table or json")?;
+                options.stats = Some(format.parse()?);
+            }
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    // Bags are what --likelihood ranks, and otherwise a mode of their own.
+    let modes: Vec<&str> = [
+        ("--format", options.format),
+        ("--stats", options.stats.is_some()),
+        ("--likelihood", options.likelihood.is_some()),
+        ("--filter", options.filter.is_some()),
+        ("--min-bag", options.min_bag.is_some()),
+        (
+            "--bag/--bags",
+            !options.bags.is_empty() && options.likelihood.is_none(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, given)| given.then_some(name))
+    .collect();
+    if modes.len() > 1 {
+        bail!("{} cannot be combined, pick one", modes.join(", "));
+    }
+    if options.at_least.is_some() && options.min_bag.is_none() {
+        bail!("--at-least only applies to --min-bag");
+    }
+    Ok(options)
+}
+
+fn print_likelihoods(games: &Games, bags: &[BagSpec], model: DrawModel) {
+    let resolved = likelihood_bags(games, bags);
+    for game in &games.games {
+        println!("game {}:", game.id);
+        for (rank, (bag_index, log_likelihood)) in (1..).zip(rank_bags(&resolved, game, model)) {
+            println!(
+                "  {rank}. {}: p = {:.6e} (ln p = {log_likelihood:.4})",
+                bags[bag_index],
+                log_likelihood.exp()
+            );
+        }
+    }
+}
+
+/// Cubes of colors no game mentions still make up part of what is drawn, so they are kept.
+fn likelihood_bags(games: &Games, bags: &[BagSpec]) -> Vec<Cubes> {
+    let mut colors = games.colors.clone();
+    bags.iter()
+        .map(|bag| bag.resolve_all(&mut colors))
+        .collect()
+}
+
+fn print_minimal_bag(games: &Games, at_least: usize, size: BagSize) -> anyhow::Result<()> {
+    let Some(minimal) = minimal_bag(&games.games, at_least, size) else {
+        bail!(
+            "cannot make {at_least} games possible, there are only {}",
+            games.games.len()
+        );
+    };
+    let colors: Vec<ColorId> = minimal.bag.iter().map(|(color, _)| color).collect();
+    let bag = colors
+        .iter()
+        .map(|color| format!("{}={}", games.colors.name(*color), minimal.bag.get(*color)))
+        .collect::<Vec<_>>()
+        .join(",");
+    println!(
+        "minimal bag: {bag} (total {}, power {})",
+        BagSize::Total.of(&minimal.bag),
+        BagSize::Product.of(&minimal.bag)
+    );
+    println!("possible games: [{}]", join_ids(&minimal.possible_games));
+    for color in colors {
+        println!(
+            "{} blocked by games [{}]",
+            games.colors.name(color),
+            join_ids(&minimal.blocking_games[&color])
+        );
+    }
+    Ok(())
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-enum Color {
-    Red,
-    Blue,
-    Green,
+fn join_ids(ids: &[u32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
+const PART_1_BAG: &str = "red=12,green=13,blue=14";
+
 #[derive(Debug, PartialEq, Eq)]
 struct Game {
     id: u32,
-    reveals: Vec<HashMap<Color, u32>>,
+    reveals: Vec<Cubes>,
 }
 
-fn parse_input(input: impl BufRead) -> Vec<Game> {
-    input
-        .lines()
-        .map(|line| line.unwrap())
-        .map(|line| parse_game(&line))
-        .collect()
+impl Game {
+    fn display<'a>(&'a self, colors: &'a Colors) -> GameDisplay<'a> {
+        GameDisplay { game: self, colors }
+    }
 }
 
-fn parse_game(line: &str) -> Game {
-    let (game_str, reveals_str) = line.split_once(":").unwrap();
-    let id = game_str
-        .chars()
-        .filter(|c| c.is_ascii_digit())
-        .collect::<String>()
-        .parse()
-        .unwrap();
-
-    let reveals = reveals_str
-        .split(";")
-        .map(|reveal| reveal.split(",").map(parse_color_reveal).collect())
-        .collect();
-    Game { id, reveals }
-}
-
-fn parse_color_reveal(s: &str) -> (Color, u32) {
-    let (number, color) = s.trim().split_once(" ").unwrap();
-    let color = match color {
-        "blue" => Color::Blue,
-        "green" => Color::Green,
-        "red" => Color::Red,
-        _ => panic!("unknown color {}", color),
+#[derive(Debug, Default)]
+struct Games {
+    colors: Colors,
+    games: Vec<Game>,
+}
+
+fn parse_input(input: impl BufRead) -> Result<Games, Vec<ValidationError>> {
+    let mut colors = Colors::default();
+    let mut games = Vec::new();
+    let mut errors = Vec::new();
+    let mut first_lines = HashMap::new();
+    for (line_number, line) in (1..).zip(input.lines()) {
+        let line = line.unwrap();
+        match parse_game(line_number, &line, &mut colors) {
+            Ok(game) => match first_lines.get(&game.id) {
+                Some(&first_line) => errors.push(ValidationError::DuplicateId {
+                    line: line_number,
+                    id: game.id,
+                    first_line,
+                }),
+                None => {
+                    first_lines.insert(game.id, line_number);
+                    games.push(game);
+                }
+            },
+            Err(line_errors) => errors.extend(line_errors),
+        }
+    }
+    if errors.is_empty() {
+        Ok(Games { colors, games })
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_game(
+    line_number: usize,
+    line: &str,
+    colors: &mut Colors,
+) -> Result<Game, Vec<ValidationError>> {
+    let malformed = |message: &str| ValidationError::Malformed {
+        line: line_number,
+        message: message.to_string(),
+    };
+    let Some((game_str, reveals_str)) = line.split_once(':') else {
+        return Err(vec![malformed("expected \"Game <id>: <reveals>\"")]);
     };
-    let number = number.parse().unwrap();
-    (color, number)
+    let Some(id_str) = game_str.trim().strip_prefix("Game ") else {
+        return Err(vec![malformed(
+            "expected the line to start with \"Game <id>:\"",
+        )]);
+    };
+    let mut errors = Vec::new();
+    let id_str = id_str.trim();
+    let id = Some(id_str)
+        .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|id| id.parse().ok());
+    if id.is_none() {
+        errors.push(ValidationError::InvalidId {
+            line: line_number,
+            id: id_str.to_string(),
+        });
+    }
+
+    let mut reveals = Vec::new();
+    for (reveal_number, reveal_str) in (1..).zip(reveals_str.split(';')) {
+        if reveal_str.trim().is_empty() {
+            errors.push(ValidationError::EmptyReveal {
+                line: line_number,
+                reveal: reveal_number,
+            });
+            continue;
+        }
+        let mut reveal = Cubes::default();
+        for cubes in reveal_str.split(',') {
+            match parse_color_reveal(cubes, colors) {
+                Ok((color, number)) => {
+                    if reveal.contains(color) {
+                        errors.push(ValidationError::DuplicateColor {
+                            line: line_number,
+                            reveal: reveal_number,
+                            color: colors.name(color).to_string(),
+                        });
+                    }
+                    reveal.set(color, number);
+                }
+                Err(message) => errors.push(malformed(&message)),
+            }
+        }
+        reveals.push(reveal);
+    }
+    match id {
+        Some(id) if errors.is_empty() => Ok(Game { id, reveals }),
+        _ => Err(errors),
+    }
+}
+
+fn parse_color_reveal(s: &str, colors: &mut Colors) -> Result<(ColorId, u32), String> {
+    let expected = || format!("expected \"<number> <color>\" but found {:?}", s.trim());
+    let (number, color) = s.trim().split_once(' ').ok_or_else(expected)?;
+    let color = color.trim();
+    if color.is_empty() || color.contains(char::is_whitespace) {
+        return Err(expected());
+    }
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(expected());
+    }
+    let number = number
+        .parse()
+        .map_err(|_| format!("{number} {color} is too many cubes"))?;
+    Ok((colors.intern(color), number))
 }
 
-fn part_1(games: &[Game]) -> u32 {
-    let bag: HashMap<_, _> = [(Color::Red, 12), (Color::Green, 13), (Color::Blue, 14)].into();
+fn part_1(games: &Games) -> u32 {
+    let bag = PART_1_BAG.parse().unwrap();
+    possible_game_ids(games, &bag).iter().sum()
+}
+
+fn possible_game_ids(games: &Games, bag: &BagSpec) -> Vec<u32> {
+    let bag = bag.resolve(&games.colors);
     games
+        .games
         .iter()
         .filter(|game| game_is_possible_given_bag(&bag, game))
         .map(|game| game.id)
-        .sum()
+        .collect()
 }
 
-fn part_2(games: &[Game]) -> u32 {
-    let power_of_bag = |bag: HashMap<_, u32>| bag.values().product::<u32>();
+fn part_2(games: &Games) -> u64 {
     games
+        .games
         .iter()
         .map(smallest_possible_bag)
-        .map(power_of_bag)
+        .map(|bag| power(&bag))
         .sum()
 }
 
-fn game_is_possible_given_bag(bag: &HashMap<Color, u32>, game: &Game) -> bool {
-    game.reveals
+fn power(bag: &Cubes) -> u64 {
+    bag.iter().map(|(_, number)| number as u64).product()
+}
+
+fn filter_games<'a>(games: &'a Games, query: &Query) -> Vec<&'a Game> {
+    games
+        .games
         .iter()
-        .flat_map(|reveal| reveal.iter())
-        .all(|(color, number)| number <= bag.get(color).unwrap_or(&0))
+        .filter(|game| query.matches(game, &games.colors))
+        .collect()
 }
 
-fn smallest_possible_bag(game: &Game) -> HashMap<Color, u32> {
-    let mut bag = HashMap::<Color, u32>::new();
-    let all_reveals = game.reveals.iter().flat_map(|reveal| reveal.iter());
-    for (color, number) in all_reveals {
-        let current_max = bag.entry(*color).or_insert(*number);
-        if *current_max < *number {
-            *current_max = *number;
-        }
+fn game_is_possible_given_bag(bag: &Cubes, game: &Game) -> bool {
+    game.reveals.iter().all(|reveal| reveal.fits_in(bag))
+}
+
+fn smallest_possible_bag(game: &Game) -> Cubes {
+    let mut bag = Cubes::default();
+    for reveal in &game.reveals {
+        bag.max_assign(reveal);
     }
     bag
 }
@@ -99,18 +406,20 @@ fn smallest_possible_bag(game: &Game) -> HashMap<Color, u32> {
 mod tests {
     use super::*;
     const EXAMPLE: &str = include_str!("../example.txt");
+    const COLORS_EXAMPLE: &str = include_str!("../colors_example.txt");
 
     #[test]
     fn test_parse_input_example() {
-        let games = parse_input(EXAMPLE.as_bytes());
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
+        let [red, green, blue] = ["red", "green", "blue"].map(|c| games.colors.get(c).unwrap());
         assert_eq!(
-            games[0],
+            games.games[0],
             Game {
                 id: 1,
                 reveals: vec![
-                    [(Color::Blue, 3), (Color::Red, 4)].into(),
-                    [(Color::Red, 1), (Color::Green, 2), (Color::Blue, 6)].into(),
-                    [(Color::Green, 2)].into()
+                    [(blue, 3), (red, 4)].into(),
+                    [(red, 1), (green, 2), (blue, 6)].into(),
+                    [(green, 2)].into()
                 ]
             },
         )
@@ -118,15 +427,372 @@ mod tests {
 
     #[test]
     fn test_part_1_example() {
-        let games = parse_input(EXAMPLE.as_bytes());
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
         let result = part_1(&games);
         assert_eq!(result, 8);
     }
 
     #[test]
     fn test_part_2_example() {
-        let games = parse_input(EXAMPLE.as_bytes());
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
         let result = part_2(&games);
         assert_eq!(result, 2286);
     }
+
+    #[test]
+    fn test_colors_example() {
+        let games = parse_input(COLORS_EXAMPLE.as_bytes()).unwrap();
+        let [yellow, purple] = ["yellow", "purple"].map(|c| games.colors.get(c).unwrap());
+        assert_eq!(
+            smallest_possible_bag(&games.games[0]),
+            [
+                (games.colors.get("red").unwrap(), 4),
+                (yellow, 7),
+                (purple, 2),
+                (games.colors.get("blue").unwrap(), 1),
+            ]
+            .into()
+        );
+        assert_eq!(part_1(&games), 2 + 4);
+        assert_eq!(part_2(&games), 56 + 9 + 18 + 1 + 60);
+
+        let large =
+            parse_input("Game 1: 1000 red, 1000 blue, 1000 green, 10 yellow\n".as_bytes()).unwrap();
+        assert_eq!(part_2(&large), 10_000_000_000);
+        assert_eq!(
+            stats(&large.games, &large.colors).games_by_power,
+            [(1, 10_000_000_000)]
+        );
+        // A listed color with no cubes still makes the game's power 0.
+        let zero = parse_input("Game 1: 0 red, 3 blue\nGame 2: 1 red\n".as_bytes()).unwrap();
+        assert_eq!(part_2(&zero), 1);
+
+        let bag = [(yellow, 7), (purple, 3)].into();
+        let possible: Vec<u32> = games
+            .games
+            .iter()
+            .filter(|game| game_is_possible_given_bag(&bag, game))
+            .map(|game| game.id)
+            .collect();
+        assert_eq!(possible, [3]);
+    }
+
+    #[test]
+    fn test_possible_game_ids_for_bags() {
+        let games = parse_input(COLORS_EXAMPLE.as_bytes()).unwrap();
+        for (bag, expected) in [
+            ("red=12,green=13,blue=14", vec![2, 4]),
+            ("yellow=7, purple=3", vec![3]),
+            (
+                "red=4,yellow=7,purple=2,blue=3,green=1,magenta=9",
+                vec![1, 2, 4],
+            ),
+            ("orange=2,cyan=2,blue=15,teal=1", vec![5]),
+        ] {
+            let bag: BagSpec = bag.parse().unwrap();
+            assert_eq!(possible_game_ids(&games, &bag), expected, "{bag}");
+        }
+        assert!("red=12,red=3".parse::<BagSpec>().is_err());
+        assert!("red:12".parse::<BagSpec>().is_err());
+        assert!("red=-1".parse::<BagSpec>().is_err());
+    }
+
+    #[test]
+    fn test_game_likelihood() {
+        let games =
+            parse_input("Game 1: 1 red; 1 red, 1 blue\nGame 2: 2 red\n".as_bytes()).unwrap();
+        let bags = likelihood_bags(
+            &games,
+            &["red=1,blue=1", "red=5,blue=1", "blue=3", "red=1,purple=99"]
+                .map(|bag| bag.parse().unwrap()),
+        );
+        let probability = |bag, game, model| {
+            likelihood::game_log_likelihood(&bags[bag], &games.games[game], model).exp()
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+
+        use DrawModel::*;
+        assert!(close(probability(0, 0, WithoutReplacement), 0.5));
+        assert!(close(probability(0, 0, WithReplacement), 0.25));
+        assert!(close(probability(0, 1, WithoutReplacement), 0.0));
+        assert!(close(probability(0, 1, WithReplacement), 0.25));
+        assert!(close(probability(2, 0, WithReplacement), 0.0));
+        // Purple is never revealed, but its 99 cubes make red one draw in a hundred.
+        let game = parse_input("Game 1: 1 red\n".as_bytes()).unwrap();
+        for model in [WithReplacement, WithoutReplacement] {
+            let p = likelihood::game_log_likelihood(&bags[3], &game.games[0], model).exp();
+            assert!(close(p, 0.01), "{model:?}: {p}");
+        }
+        // Bags of billions of cubes, more in total than a u32 holds, are as quick as small ones.
+        let huge = likelihood_bags(
+            &games,
+            &["red=1000000000", "red=4000000000,blue=4000000000"].map(|bag| bag.parse().unwrap()),
+        );
+        for model in [WithReplacement, WithoutReplacement] {
+            let p = |bag: usize| likelihood::game_log_likelihood(&huge[bag], &game.games[0], model);
+            assert!(close(p(0).exp(), 1.0), "{model:?}");
+            assert!(close(p(1).exp(), 0.5), "{model:?}");
+        }
+        // C(5,1)/C(6,1) for the first reveal, C(5,1)C(1,1)/C(6,2) for the second.
+        assert!(close(
+            probability(1, 0, WithoutReplacement),
+            5.0 / 6.0 * 5.0 / 15.0
+        ));
+
+        let ranking = |game, model| -> Vec<usize> {
+            rank_bags(&bags, &games.games[game], model)
+                .into_iter()
+                .map(|(bag, _)| bag)
+                .collect()
+        };
+        assert_eq!(ranking(0, WithoutReplacement), [0, 1, 2, 3]);
+        assert_eq!(ranking(1, WithoutReplacement), [1, 0, 2, 3]);
+        assert_eq!(ranking(1, WithReplacement), [1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn test_minimal_bag_example() {
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
+        let [red, green, blue] = ["red", "green", "blue"].map(|c| games.colors.get(c).unwrap());
+
+        let all = minimal_bag(&games.games, 5, BagSize::Total).unwrap();
+        assert_eq!(all.bag, [(red, 20), (green, 13), (blue, 15)].into());
+        assert_eq!(all.possible_games, [1, 2, 3, 4, 5]);
+        assert_eq!(
+            all.blocking_games,
+            [(red, vec![3]), (green, vec![3]), (blue, vec![4])].into()
+        );
+
+        let by_total = minimal_bag(&games.games, 4, BagSize::Total).unwrap();
+        assert_eq!(by_total.bag, [(red, 14), (green, 3), (blue, 15)].into());
+        assert_eq!(by_total.possible_games, [1, 2, 4, 5]);
+
+        let by_product = minimal_bag(&games.games, 4, BagSize::Product).unwrap();
+        assert_eq!(by_product.bag, by_total.bag);
+
+        for size in [BagSize::Total, BagSize::Product] {
+            let three = minimal_bag(&games.games, 3, size).unwrap();
+            assert_eq!(three.bag, [(red, 6), (green, 3), (blue, 6)].into());
+            assert_eq!(three.possible_games, [1, 2, 5]);
+            assert_eq!(
+                three.blocking_games,
+                [(red, vec![5]), (green, vec![2, 5]), (blue, vec![1])].into()
+            );
+        }
+
+        assert_eq!(minimal_bag(&games.games, 6, BagSize::Total), None);
+    }
+
+    #[test]
+    fn test_filter_games() {
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
+        let matching_ids = |query: &str| -> Vec<u32> {
+            filter_games(&games, &query.parse().unwrap())
+                .iter()
+                .map(|game| game.id)
+                .collect()
+        };
+        assert_eq!(matching_ids("max(red) > 12"), [3, 4]);
+        assert_eq!(matching_ids("power > 1000"), [3]);
+        assert_eq!(matching_ids("power >= 630"), [3, 4]);
+        assert_eq!(matching_ids("max(red) > 10 && count(reveals) >= 3"), [3, 4]);
+        assert_eq!(matching_ids("count(reveals) == 2 || id == 1"), [1, 5]);
+        assert_eq!(matching_ids("!(sum(blue) >= 6)"), [5]);
+        assert_eq!(matching_ids("min(red) == 0 && count(red) == 2"), [1]);
+        assert_eq!(
+            matching_ids("count(colors) == 3 && max(purple) == 0"),
+            [1, 2, 3, 4, 5]
+        );
+        assert_eq!(matching_ids("1 < 0"), Vec::<u32>::new());
+
+        for invalid in [
+            "max(red) >",
+            "max red > 1",
+            "foo > 1",
+            "id > 1 &&",
+            "(id > 1",
+            "id = 1",
+        ] {
+            assert!(invalid.parse::<Query>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_stats_example() {
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
+        let report = stats(&games.games, &games.colors);
+        assert_eq!((report.games, report.reveals), (5, 14));
+        let names: Vec<&str> = report
+            .colors
+            .iter()
+            .map(|color| color.name.as_str())
+            .collect();
+        assert_eq!(names, ["blue", "red", "green"]);
+        let red = &report.colors[1];
+        assert_eq!(red.max, 20);
+        assert_eq!(red.mean, Some(61.0 / 14.0));
+        assert_eq!(red.histogram[&0], 3);
+        assert_eq!(red.histogram.values().sum::<usize>(), 14);
+        assert_eq!(
+            report.games_by_power,
+            [(3, 1560), (4, 630), (1, 48), (5, 36), (2, 12)]
+        );
+        assert_eq!(report.correlations[0][0], Some(1.0));
+        let (blue_red, red_blue) = (report.correlations[0][1], report.correlations[1][0]);
+        assert_eq!(blue_red, red_blue);
+        assert!((blue_red.unwrap() - 0.5858).abs() < 1e-4);
+
+        let json = report.json();
+        assert!(json.starts_with("{\"games\":5,\"reveals\":14,\"colors\":[{\"name\":\"blue\""));
+        assert!(json.contains("\"games_by_power\":[{\"id\":3,\"power\":1560},"));
+
+        let single = parse_input("Game 1: 2 red".as_bytes()).unwrap();
+        let single = stats(&single.games, &single.colors);
+        assert_eq!(single.correlations, [[None]]);
+        assert!(single.json().ends_with("\"correlations\":[[null]]}"));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let input = "\
+Game 1x2: 3 blue
+Game 3: 1 red, 2 red; ; 4 green
+Game 6: 1 blue
+Game 6: 2 blue
+Game 4 3 blue
+Game 5: 3, blue 4; 2 green
+";
+        let errors = parse_input(input.as_bytes()).unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "line 1: game id \"1x2\" is not a number",
+                "line 2: reveal 1 lists red more than once",
+                "line 2: reveal 2 is empty",
+                "line 4: game 6 was already defined on line 3",
+                "line 5: expected \"Game <id>: <reveals>\"",
+                "line 6: expected \"<number> <color>\" but found \"3\"",
+                "line 6: expected \"<number> <color>\" but found \"blue 4\"",
+            ]
+        );
+        assert!(parse_input("Game 1:".as_bytes()).is_err());
+        assert!(parse_input("Game 1: 3 red;".as_bytes()).is_err());
+        assert!(parse_input("Game +1: 3 red".as_bytes()).is_err());
+        assert!(parse_input("Game 1: 3 dark red".as_bytes()).is_err());
+        assert!(parse_input("Game 1: 0 red, 3 blue".as_bytes()).is_ok());
+        assert!(parse_input("Game 1: 0 red, 3 blue, 0 red".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_modes() {
+        let parse = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        let error = parse(&[
+            "--format", "--stats", "json", "--filter", "id > 5", "--bag", "red=1",
+        ])
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--format, --stats, --filter, --bag/--bags cannot be combined, pick one"
+        );
+        assert!(parse(&["--likelihood", "with-replacement", "--bag", "red=1"]).is_ok());
+        assert!(parse(&["--min-bag", "total", "--bag", "red=1"]).is_err());
+        assert!(parse(&["--stats", "table"]).is_ok());
+        assert!(parse(&["--at-least", "1"]).is_err());
+        assert!(parse(&["--min-bag", "product", "--at-least", "1"]).is_ok());
+    }
+
+    #[test]
+    fn test_display_round_trip_example() {
+        let games = parse_input(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(
+            games.games[0].display(&games.colors).to_string(),
+            "Game 1: 3 blue, 4 red; 6 blue, 1 red, 2 green; 2 green"
+        );
+        let zero = parse_input("Game 1: 0 red; 2 blue".as_bytes()).unwrap();
+        assert_eq!(
+            zero.games[0].display(&zero.colors).to_string(),
+            "Game 1: 0 red; 2 blue"
+        );
+        assert_ne!(zero.games[0].reveals[0], Cubes::default());
+        for example in [EXAMPLE, COLORS_EXAMPLE] {
+            let games = parse_input(example.as_bytes()).unwrap();
+            let formatted = format_games(&games);
+            let reparsed = parse_input(formatted.as_bytes()).unwrap();
+            assert_eq!(reparsed.games, games.games);
+            assert_eq!(reparsed.colors, games.colors);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip_generated() {
+        const COLOR_NAMES: [&str; 8] = [
+            "red", "green", "blue", "yellow", "purple", "orange", "cyan", "teal",
+        ];
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        for _ in 0..200 {
+            let mut colors = Colors::default();
+            let mut games = Vec::new();
+            let mut id = 0;
+            for _ in 0..1 + next(10) {
+                id += 1 + next(1000) as u32;
+                let reveals = (0..1 + next(6))
+                    .map(|_| {
+                        let mut reveal = Cubes::default();
+                        loop {
+                            let color = colors.intern(COLOR_NAMES[next(8) as usize]);
+                            reveal.set(color, next(100) as u32);
+                            if reveal.iter().count() == COLOR_NAMES.len() || next(3) == 0 {
+                                break;
+                            }
+                        }
+                        reveal
+                    })
+                    .collect();
+                games.push(Game { id, reveals });
+            }
+            let games = Games { colors, games };
+            let reparsed = parse_input(format_games(&games).as_bytes()).unwrap();
+            assert_eq!(named(&reparsed), named(&games));
+            assert_eq!(format_games(&reparsed), format_games(&games));
+        }
+    }
+
+    fn format_games(games: &Games) -> String {
+        games
+            .games
+            .iter()
+            .map(|game| format!("{}\n", game.display(&games.colors)))
+            .collect()
+    }
+
+    type NamedReveals = Vec<Vec<(String, u32)>>;
+
+    fn named(games: &Games) -> Vec<(u32, NamedReveals)> {
+        games
+            .games
+            .iter()
+            .map(|game| {
+                let reveals = game
+                    .reveals
+                    .iter()
+                    .map(|reveal| {
+                        let mut cubes: Vec<(String, u32)> = reveal
+                            .iter()
+                            .map(|(color, number)| (games.colors.name(color).to_string(), number))
+                            .collect();
+                        cubes.sort();
+                        cubes
+                    })
+                    .collect();
+                (game.id, reveals)
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,161 @@
+use crate::{
+    color::{ColorId, Cubes},
+    game_is_possible_given_bag, smallest_possible_bag, Game,
+};
+use anyhow::bail;
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+};
+
+/// What "smallest" means when comparing bags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BagSize {
+    /// The total number of cubes.
+    Total,
+    /// The product of the counts of every color in the bag, i.e. its power.
+    Product,
+}
+
+impl BagSize {
+    pub fn of(self, bag: &Cubes) -> u64 {
+        self.fold(bag.iter().map(|(_, number)| number))
+    }
+
+    fn fold(self, counts: impl Iterator<Item = u32>) -> u64 {
+        let counts = counts.filter(|count| *count > 0).map(u64::from);
+        match self {
+            BagSize::Total => counts.sum(),
+            BagSize::Product => counts.product(),
+        }
+    }
+}
+
+impl FromStr for BagSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "total" => Ok(Self::Total),
+            "product" => Ok(Self::Product),
+            _ => bail!("unknown bag size {s}, expected total or product"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MinimalBag {
+    pub bag: Cubes,
+    /// Ids of every game that is possible with `bag`.
+    pub possible_games: Vec<u32>,
+    /// For each color in the bag, the ids of the games that need all of its cubes.
+    pub blocking_games: HashMap<ColorId, Vec<u32>>,
+}
+
+/// The smallest bag that makes at least `at_least` of `games` possible, or `None` if there
+/// are fewer games than that.
+pub fn minimal_bag(games: &[Game], at_least: usize, size: BagSize) -> Option<MinimalBag> {
+    if at_least > games.len() {
+        return None;
+    }
+    let needs: Vec<Cubes> = games.iter().map(smallest_possible_bag).collect();
+    let bag = if at_least == games.len() {
+        max_per_color(needs.iter())
+    } else {
+        let colors: Vec<ColorId> = needs
+            .iter()
+            .flat_map(|need| need.iter().map(|(color, _)| color))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut search = Search {
+            needs: &needs,
+            colors: &colors,
+            at_least,
+            size,
+            thresholds: Vec::with_capacity(colors.len()),
+            best: None,
+        };
+        search.run((0..needs.len()).collect());
+        let (_, thresholds) = search.best?;
+        let bag: Cubes = colors.into_iter().zip(thresholds).collect();
+        // Shrink to what the games that fit actually need.
+        max_per_color(needs.iter().filter(|need| need.fits_in(&bag)))
+    };
+
+    let possible: Vec<(&Game, &Cubes)> = games
+        .iter()
+        .zip(&needs)
+        .filter(|(game, _)| game_is_possible_given_bag(&bag, game))
+        .collect();
+    let blocking_games = bag
+        .iter()
+        .map(|(color, count)| {
+            let blocking = possible
+                .iter()
+                .filter(|(_, need)| need.get(color) == count)
+                .map(|(game, _)| game.id)
+                .collect();
+            (color, blocking)
+        })
+        .collect();
+    Some(MinimalBag {
+        possible_games: possible.iter().map(|(game, _)| game.id).collect(),
+        blocking_games,
+        bag,
+    })
+}
+
+fn max_per_color<'a>(needs: impl Iterator<Item = &'a Cubes>) -> Cubes {
+    let mut bag = Cubes::default();
+    for need in needs {
+        bag.max_assign(need);
+    }
+    bag
+}
+
+/// Branch and bound over one cube count per color. Each count is taken from the needs of the
+/// games still in play, in increasing order, so the first count that is too costly ends the
+/// branch.
+struct Search<'a> {
+    needs: &'a [Cubes],
+    colors: &'a [ColorId],
+    at_least: usize,
+    size: BagSize,
+    thresholds: Vec<u32>,
+    best: Option<(u64, Vec<u32>)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, candidates: Vec<usize>) {
+        let Some(&color) = self.colors.get(self.thresholds.len()) else {
+            let cost = self.size.fold(self.thresholds.iter().copied());
+            if self.best.as_ref().is_none_or(|(best, _)| cost < *best) {
+                self.best = Some((cost, self.thresholds.clone()));
+            }
+            return;
+        };
+        let needs = self.needs;
+        let need = |game: usize| needs[game].get(color);
+        let counts: BTreeSet<u32> = candidates.iter().map(|&game| need(game)).collect();
+        for count in counts {
+            let cost = self
+                .size
+                .fold(self.thresholds.iter().copied().chain([count]));
+            if self.best.as_ref().is_some_and(|(best, _)| cost >= *best) {
+                break;
+            }
+            let remaining: Vec<usize> = candidates
+                .iter()
+                .copied()
+                .filter(|&game| need(game) <= count)
+                .collect();
+            if remaining.len() < self.at_least {
+                continue;
+            }
+            self.thresholds.push(count);
+            self.run(remaining);
+            self.thresholds.pop();
+        }
+    }
+}
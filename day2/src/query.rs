@@ -0,0 +1,247 @@
+use crate::{color::Colors, power, smallest_possible_bag, Game};
+use anyhow::{bail, Context};
+use std::str::FromStr;
+
+/// A predicate over games, e.g. `max(red) > 10 && count(reveals) >= 3`.
+///
+/// Values are numbers, `id`, `power`, `max(color)`, `min(color)`, `sum(color)`,
+/// `count(color)` (reveals showing that color), `count(reveals)` and `count(colors)`.
+/// They are compared with `<`, `<=`, `>`, `>=`, `==` or `!=` and combined with `&&`, `||`,
+/// `!` and parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Or(Box<Query>, Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Compare(Value, Comparison, Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(u64),
+    Id,
+    Power,
+    Reveals,
+    Colors,
+    Max(String),
+    Min(String),
+    Sum(String),
+    Count(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Query {
+    pub fn matches(&self, game: &Game, colors: &Colors) -> bool {
+        match self {
+            Query::Or(a, b) => a.matches(game, colors) || b.matches(game, colors),
+            Query::And(a, b) => a.matches(game, colors) && b.matches(game, colors),
+            Query::Not(query) => !query.matches(game, colors),
+            Query::Compare(a, comparison, b) => {
+                let (a, b) = (a.evaluate(game, colors), b.evaluate(game, colors));
+                match comparison {
+                    Comparison::Less => a < b,
+                    Comparison::LessOrEqual => a <= b,
+                    Comparison::Greater => a > b,
+                    Comparison::GreaterOrEqual => a >= b,
+                    Comparison::Equal => a == b,
+                    Comparison::NotEqual => a != b,
+                }
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Colors the input never mentions count as zero cubes in every reveal.
+    pub fn evaluate(&self, game: &Game, colors: &Colors) -> u64 {
+        let per_reveal = |color: &str| -> Vec<u64> {
+            let color = colors.get(color);
+            game.reveals
+                .iter()
+                .map(|reveal| color.map_or(0, |color| reveal.get(color) as u64))
+                .collect()
+        };
+        match self {
+            Value::Number(number) => *number,
+            Value::Id => game.id as u64,
+            Value::Power => power(&smallest_possible_bag(game)),
+            Value::Reveals => game.reveals.len() as u64,
+            Value::Colors => smallest_possible_bag(game).iter().count() as u64,
+            Value::Max(color) => per_reveal(color).into_iter().max().unwrap_or(0),
+            Value::Min(color) => per_reveal(color).into_iter().min().unwrap_or(0),
+            Value::Sum(color) => per_reveal(color).into_iter().sum(),
+            Value::Count(color) => per_reveal(color).iter().filter(|n| **n > 0).count() as u64,
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let query = parser.or()?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected {token:?} after the end of the query");
+        }
+        Ok(query)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 11] = ["&&", "||", "<=", ">=", "==", "!=", "<", ">", "!", "(", ")"];
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse()
+                .with_context(|| format!("number {} is too large", &rest[..len]))?;
+            tokens.push(Token::Number(number));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if let Some(op) = OPERATORS.into_iter().find(|op| rest.starts_with(op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            bail!("unexpected character {c:?} in query");
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .context("unexpected end of query")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        let matched = self.peek() == Some(&Token::Op(op));
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, op: &'static str) -> anyhow::Result<()> {
+        if !self.eat(op) {
+            bail!("expected {op:?} but found {:?}", self.peek());
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> anyhow::Result<Query> {
+        let mut query = self.and()?;
+        while self.eat("||") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Query> {
+        let mut query = self.not()?;
+        while self.eat("&&") {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Query> {
+        if self.eat("!") {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        if self.eat("(") {
+            let query = self.or()?;
+            self.expect(")")?;
+            return Ok(query);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Query> {
+        let a = self.value()?;
+        let comparison = match self.next()? {
+            Token::Op("<") => Comparison::Less,
+            Token::Op("<=") => Comparison::LessOrEqual,
+            Token::Op(">") => Comparison::Greater,
+            Token::Op(">=") => Comparison::GreaterOrEqual,
+            Token::Op("==") => Comparison::Equal,
+            Token::Op("!=") => Comparison::NotEqual,
+            token => bail!("expected a comparison but found {token:?}"),
+        };
+        let b = self.value()?;
+        Ok(Query::Compare(a, comparison, b))
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        let name = match self.next()? {
+            Token::Number(number) => return Ok(Value::Number(number)),
+            Token::Ident(name) => name,
+            token => bail!("expected a value but found {token:?}"),
+        };
+        match name.as_str() {
+            "id" => return Ok(Value::Id),
+            "power" => return Ok(Value::Power),
+            "count" | "max" | "min" | "sum" => {}
+            _ => bail!("unknown value {name}"),
+        }
+        self.expect("(")?;
+        let argument = match self.next()? {
+            Token::Ident(argument) => argument,
+            token => bail!("expected a color in {name}(...) but found {token:?}"),
+        };
+        self.expect(")")?;
+        Ok(match (name.as_str(), argument.as_str()) {
+            ("count", "reveals") => Value::Reveals,
+            ("count", "colors") => Value::Colors,
+            ("count", _) => Value::Count(argument),
+            ("max", _) => Value::Max(argument),
+            ("min", _) => Value::Min(argument),
+            _ => Value::Sum(argument),
+        })
+    }
+}
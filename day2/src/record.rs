@@ -0,0 +1,84 @@
+use crate::{color::Colors, Game};
+use std::fmt;
+
+/// A problem with one line of the game records. Lines and reveals are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    Malformed {
+        line: usize,
+        message: String,
+    },
+    InvalidId {
+        line: usize,
+        id: String,
+    },
+    DuplicateId {
+        line: usize,
+        id: u32,
+        first_line: usize,
+    },
+    EmptyReveal {
+        line: usize,
+        reveal: usize,
+    },
+    DuplicateColor {
+        line: usize,
+        reveal: usize,
+        color: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Malformed { line, message } => write!(f, "line {line}: {message}"),
+            ValidationError::InvalidId { line, id } => {
+                write!(f, "line {line}: game id {id:?} is not a number")
+            }
+            ValidationError::DuplicateId {
+                line,
+                id,
+                first_line,
+            } => write!(
+                f,
+                "line {line}: game {id} was already defined on line {first_line}"
+            ),
+            ValidationError::EmptyReveal { line, reveal } => {
+                write!(f, "line {line}: reveal {reveal} is empty")
+            }
+            ValidationError::DuplicateColor {
+                line,
+                reveal,
+                color,
+            } => write!(
+                f,
+                "line {line}: reveal {reveal} lists {color} more than once"
+            ),
+        }
+    }
+}
+
+/// Writes a game back out in the input format, with each reveal's colors in interning order
+/// so that parsing the output with a fresh color table assigns the same color ids.
+pub struct GameDisplay<'a> {
+    pub game: &'a Game,
+    pub colors: &'a Colors,
+}
+
+impl fmt::Display for GameDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Game {}:", self.game.id)?;
+        for (i, reveal) in self.game.reveals.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            for (j, (color, number)) in reveal.iter().enumerate() {
+                if j > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, " {number} {}", self.colors.name(color))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{color::Colors, power, smallest_possible_bag, Game};
+use anyhow::bail;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown stats format {s}, expected table or json"),
+        }
+    }
+}
+
+/// Descriptive statistics over every reveal of every game. A color missing from a reveal
+/// counts as zero cubes of that color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub games: usize,
+    pub reveals: usize,
+    pub colors: Vec<ColorStats>,
+    /// Game ids with their power, highest power first.
+    pub games_by_power: Vec<(u32, u64)>,
+    /// Pearson correlation of the per-reveal counts of each pair of colors, indexed like
+    /// `colors`. `None` when either color has the same count in every reveal.
+    pub correlations: Vec<Vec<Option<f64>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorStats {
+    pub name: String,
+    /// Number of reveals for each cube count, including reveals without the color.
+    pub histogram: BTreeMap<u32, usize>,
+    /// Mean cubes per reveal, `None` when there are no reveals.
+    pub mean: Option<f64>,
+    pub max: u32,
+}
+
+pub fn stats(games: &[Game], colors: &Colors) -> Stats {
+    let reveals: Vec<_> = games.iter().flat_map(|game| &game.reveals).collect();
+    let counts: Vec<Vec<u32>> = colors
+        .iter()
+        .map(|(color, _)| reveals.iter().map(|reveal| reveal.get(color)).collect())
+        .collect();
+
+    let color_stats = colors
+        .iter()
+        .zip(&counts)
+        .map(|((_, name), counts)| {
+            let mut histogram = BTreeMap::new();
+            for count in counts {
+                *histogram.entry(*count).or_insert(0) += 1;
+            }
+            ColorStats {
+                name: name.to_string(),
+                histogram,
+                mean: mean(counts),
+                max: counts.iter().copied().max().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let mut games_by_power: Vec<(u32, u64)> = games
+        .iter()
+        .map(|game| (game.id, power(&smallest_possible_bag(game))))
+        .collect();
+    games_by_power
+        .sort_by(|(a_id, a_power), (b_id, b_power)| b_power.cmp(a_power).then(a_id.cmp(b_id)));
+
+    let correlations = counts
+        .iter()
+        .map(|a| counts.iter().map(|b| correlation(a, b)).collect())
+        .collect();
+
+    Stats {
+        games: games.len(),
+        reveals: reveals.len(),
+        colors: color_stats,
+        games_by_power,
+        correlations,
+    }
+}
+
+fn mean(counts: &[u32]) -> Option<f64> {
+    if counts.is_empty() {
+        return None;
+    }
+    Some(counts.iter().map(|count| *count as f64).sum::<f64>() / counts.len() as f64)
+}
+
+fn correlation(a: &[u32], b: &[u32]) -> Option<f64> {
+    let (mean_a, mean_b) = (mean(a)?, mean(b)?);
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (a, b) in a.iter().zip(b) {
+        let (da, db) = (*a as f64 - mean_a, *b as f64 - mean_b);
+        covariance += da * db;
+        variance_a += da * da;
+        variance_b += db * db;
+    }
+    if variance_a == 0.0 || variance_b == 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_a * variance_b).sqrt())
+}
+
+impl Stats {
+    pub fn json(&self) -> String {
+        let number = |value: Option<f64>| value.map_or("null".to_string(), |v| format!("{v}"));
+        let colors: Vec<String> = self
+            .colors
+            .iter()
+            .map(|color| {
+                let histogram: Vec<String> = color
+                    .histogram
+                    .iter()
+                    .map(|(count, reveals)| format!("\"{count}\":{reveals}"))
+                    .collect();
+                format!(
+                    "{{\"name\":{},\"histogram\":{{{}}},\"mean\":{},\"max\":{}}}",
+                    json_string(&color.name),
+                    histogram.join(","),
+                    number(color.mean),
+                    color.max
+                )
+            })
+            .collect();
+        let games_by_power: Vec<String> = self
+            .games_by_power
+            .iter()
+            .map(|(id, power)| format!("{{\"id\":{id},\"power\":{power}}}"))
+            .collect();
+        let correlations: Vec<String> = self
+            .correlations
+            .iter()
+            .map(|row| {
+                let row: Vec<String> = row.iter().map(|value| number(*value)).collect();
+                format!("[{}]", row.join(","))
+            })
+            .collect();
+        format!(
+            "{{\"games\":{},\"reveals\":{},\"colors\":[{}],\"games_by_power\":[{}],\"correlations\":[{}]}}",
+            self.games,
+            self.reveals,
+            colors.join(","),
+            games_by_power.join(","),
+            correlations.join(",")
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} games, {} reveals", self.games, self.reveals)?;
+        let width = self
+            .colors
+            .iter()
+            .map(|color| color.name.len())
+            .chain([5])
+            .max()
+            .unwrap_or(5);
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<width$} {:>8} {:>6}  histogram",
+            "color", "mean", "max"
+        )?;
+        for color in &self.colors {
+            let mean = color
+                .mean
+                .map_or("-".to_string(), |mean| format!("{mean:.2}"));
+            let histogram: Vec<String> = color
+                .histogram
+                .iter()
+                .map(|(count, reveals)| format!("{count}x{reveals}"))
+                .collect();
+            writeln!(
+                f,
+                "{:<width$} {mean:>8} {:>6}  {}",
+                color.name,
+                color.max,
+                histogram.join(" ")
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "correlations")?;
+        let column = width.max(6);
+        write!(f, "{:<width$}", "")?;
+        for color in &self.colors {
+            write!(f, " {:>column$}", color.name)?;
+        }
+        writeln!(f)?;
+        for (color, row) in self.colors.iter().zip(&self.correlations) {
+            write!(f, "{:<width$}", color.name)?;
+            for value in row {
+                let value = value.map_or("-".to_string(), |value| format!("{value:+.3}"));
+                write!(f, " {value:>column$}")?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "games by power")?;
+        for (id, power) in &self.games_by_power {
+            writeln!(f, "game {id}: {power}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    gear::GearRule,
+    parse_grid, part_1, part_2,
+    schematic::{Adjacency, CellRules, Schematic},
+    storage::{DenseStorage, HashStorage, Storage},
+    stream,
+};
+use anyhow::ensure;
+use std::time::{Duration, Instant};
+
+/// Times building and both parts with each storage, and the streaming solver, on a generated
+/// `size`×`size` schematic.
+pub fn run(size: usize) -> anyhow::Result<()> {
+    let input = generate(size);
+    println!(
+        "generated {size}x{size} schematic, {} MB",
+        input.len() >> 20
+    );
+
+    let hash = measure::<HashStorage>(&input);
+    let dense = measure::<DenseStorage>(&input);
+    ensure!(
+        hash.answers == dense.answers,
+        "dense storage gave {:?} but hash storage gave {:?}",
+        dense.answers,
+        hash.answers
+    );
+    let (streamed, stream_time) = time(|| {
+        stream::solve(
+            input.as_bytes(),
+            &CellRules::default(),
+            Adjacency::default(),
+            &GearRule::default(),
+        )
+    });
+    let streamed = streamed?;
+    ensure!(
+        streamed == hash.answers,
+        "streaming gave {streamed:?} but hash storage gave {:?}",
+        hash.answers
+    );
+    println!("part 1: {}, part 2: {}", hash.answers.0, hash.answers.1);
+
+    println!(
+        "{:<8} {:>10} {:>10} {:>10}",
+        "", "build", "part 1", "part 2"
+    );
+    for (name, run) in [("hash", &hash), ("dense", &dense)] {
+        let [build, part_1, part_2] = run.times;
+        println!("{name:<8} {build:>10.2?} {part_1:>10.2?} {part_2:>10.2?}");
+    }
+    let total = |run: &Run| run.times.iter().sum::<Duration>().as_secs_f64();
+    println!("speedup: {:.1}x", total(&hash) / total(&dense));
+    println!("stream   {stream_time:>10.2?} for both parts, parsing included");
+    Ok(())
+}
+
+struct Run {
+    answers: (i128, i128),
+    times: [Duration; 3],
+}
+
+fn measure<S: Storage>(input: &str) -> Run {
+    let grid = parse_grid(input.as_bytes(), &CellRules::default());
+    let (schematic, build_time) = time(|| Schematic::<S>::from(grid));
+    let (part_1, part_1_time) = time(|| part_1(&schematic, Adjacency::default()));
+    let (part_2, part_2_time) =
+        time(|| part_2(&schematic, Adjacency::default(), &GearRule::default()));
+    Run {
+        answers: (part_1, part_2),
+        times: [build_time, part_1_time, part_2_time],
+    }
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+/// Mostly empty cells, with runs of up to three digits and a sprinkling of symbols.
+pub fn generate(size: usize) -> String {
+    let mut state = 0xd1b5_4a32_d192_ed03_u64;
+    let mut next = move |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % bound
+    };
+    const SYMBOLS: &[u8] = b"*#+$/=%@&-";
+    let mut input = String::with_capacity((size + 1) * size);
+    for _ in 0..size {
+        let mut col = 0;
+        while col < size {
+            match next(10) {
+                0..=1 => {
+                    for _ in 0..(1 + next(3) as usize).min(size - col) {
+                        input.push((b'0' + next(10) as u8) as char);
+                        col += 1;
+                    }
+                    if col < size {
+                        input.push('.');
+                        col += 1;
+                    }
+                }
+                2 => {
+                    input.push(SYMBOLS[next(SYMBOLS.len() as u64) as usize] as char);
+                    col += 1;
+                }
+                _ => {
+                    input.push('.');
+                    col += 1;
+                }
+            }
+        }
+        input.push('\n');
+    }
+    input
+}
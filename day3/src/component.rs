@@ -0,0 +1,81 @@
+use crate::{
+    is_part_number,
+    schematic::{Adjacency, EntityId, EntityValue, Position, Schematic},
+    storage::Storage,
+};
+use std::{cmp::Reverse, collections::HashSet};
+
+/// Entities linked to each other through chains of adjacent entities.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Ordered by the row and column of each entity's first cell.
+    pub entities: Vec<EntityId>,
+    pub top_left: Position,
+    pub bottom_right: Position,
+    pub part_number_sum: i128,
+}
+
+/// Every component of the schematic, largest first and then from the top left.
+pub fn components<S: Storage>(schematic: &Schematic<S>, adjacency: Adjacency) -> Vec<Component> {
+    let mut seen = HashSet::new();
+    let mut components = Vec::new();
+    for entity in schematic.entities() {
+        if !seen.insert(entity.id) {
+            continue;
+        }
+        let mut entities = vec![entity.id];
+        let mut next = 0;
+        while let Some(&id) = entities.get(next) {
+            next += 1;
+            for pos in schematic.get_entity_positions(&id) {
+                for neighbour in schematic.neighbours(&pos, adjacency) {
+                    if let Some(other) = schematic.get_entity_at_position(&neighbour) {
+                        if seen.insert(other.id) {
+                            entities.push(other.id);
+                        }
+                    }
+                }
+            }
+        }
+        components.push(component(schematic, entities, adjacency));
+    }
+    components.sort_by_key(|component| (Reverse(component.entities.len()), component.top_left));
+    components
+}
+
+fn component<S: Storage>(
+    schematic: &Schematic<S>,
+    mut entities: Vec<EntityId>,
+    adjacency: Adjacency,
+) -> Component {
+    entities.sort_by_key(|id| schematic.get_entity_positions(id).next());
+
+    let positions: Vec<Position> = entities
+        .iter()
+        .flat_map(|id| schematic.get_entity_positions(id))
+        .collect();
+    let rows = positions.iter().map(Position::row);
+    let cols = positions.iter().map(Position::col);
+    let top_left = Position::new(
+        rows.clone().min().unwrap_or(0),
+        cols.clone().min().unwrap_or(0),
+    );
+    let bottom_right = Position::new(rows.max().unwrap_or(0), cols.max().unwrap_or(0));
+
+    let part_number_sum = entities
+        .iter()
+        .filter_map(|id| match schematic.get_entity(id)?.value {
+            EntityValue::Number(num) => Some((id, num)),
+            _ => None,
+        })
+        .filter(|(id, _)| is_part_number(schematic, id, adjacency))
+        .map(|(_, num)| num)
+        .sum();
+
+    Component {
+        entities,
+        top_left,
+        bottom_right,
+        part_number_sum,
+    }
+}
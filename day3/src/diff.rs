@@ -0,0 +1,248 @@
+use crate::{
+    gear::GearRule,
+    is_part_number, part_1, part_2,
+    schematic::{Adjacency, EntityId, EntityValue, Position, Schematic},
+    storage::Storage,
+};
+use anyhow::bail;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown diff format {s}, expected text or json"),
+        }
+    }
+}
+
+/// What changed between two revisions of a schematic. Entities are matched by the cell they
+/// start at, and every list is in row-major order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff {
+    /// Part 1 and part 2 before and after.
+    pub totals: [(i128, i128); 2],
+    pub added: Vec<(Position, EntityValue)>,
+    pub removed: Vec<(Position, EntityValue)>,
+    /// Entities starting at the same cell with a different value or length.
+    pub changed: Vec<(Position, EntityValue, EntityValue)>,
+    /// Numbers in both revisions that are only parts in the new one, with their new value.
+    pub gained_part: Vec<(Position, i128)>,
+    /// Numbers in both revisions that are only parts in the old one, with their old value.
+    pub lost_part: Vec<(Position, i128)>,
+    /// Gears whose ratio changed, with `None` where the symbol was not a gear.
+    pub gears: Vec<(Position, Option<i128>, Option<i128>)>,
+}
+
+pub fn diff<S: Storage, T: Storage>(
+    old: &Schematic<S>,
+    new: &Schematic<T>,
+    adjacency: Adjacency,
+    gear_rule: &GearRule,
+) -> Diff {
+    let old_starts = starts(old);
+    let new_starts = starts(new);
+    let mut diff = Diff {
+        totals: [
+            (part_1(old, adjacency), part_2(old, adjacency, gear_rule)),
+            (part_1(new, adjacency), part_2(new, adjacency, gear_rule)),
+        ],
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        gained_part: Vec::new(),
+        lost_part: Vec::new(),
+        gears: Vec::new(),
+    };
+
+    let cells: BTreeSet<_> = old_starts.keys().chain(new_starts.keys()).collect();
+    for &pos in cells {
+        match (old_starts.get(&pos), new_starts.get(&pos)) {
+            (Some(&before), Some(&after)) => {
+                let (before_value, after_value) = (value(old, before), value(new, after));
+                let same_cells = old
+                    .get_entity_positions(&before)
+                    .eq(new.get_entity_positions(&after));
+                if before_value != after_value || !same_cells {
+                    diff.changed
+                        .push((pos, before_value.clone(), after_value.clone()));
+                }
+                if let (EntityValue::Number(before_num), EntityValue::Number(after_num)) =
+                    (before_value, after_value)
+                {
+                    match (
+                        is_part_number(old, &before, adjacency),
+                        is_part_number(new, &after, adjacency),
+                    ) {
+                        (false, true) => diff.gained_part.push((pos, *after_num)),
+                        (true, false) => diff.lost_part.push((pos, *before_num)),
+                        _ => {}
+                    }
+                }
+            }
+            (Some(&before), None) => diff.removed.push((pos, value(old, before).clone())),
+            (None, Some(&after)) => diff.added.push((pos, value(new, after).clone())),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    let old_gears = ratios(old, adjacency, gear_rule);
+    let new_gears = ratios(new, adjacency, gear_rule);
+    let cells: BTreeSet<_> = old_gears.keys().chain(new_gears.keys()).collect();
+    for &pos in cells {
+        let (before, after) = (old_gears.get(&pos).copied(), new_gears.get(&pos).copied());
+        if before != after {
+            diff.gears.push((pos, before, after));
+        }
+    }
+    diff
+}
+
+/// Every entity by its first cell.
+fn starts<S: Storage>(schematic: &Schematic<S>) -> BTreeMap<Position, EntityId> {
+    schematic
+        .entities_by_position()
+        .into_iter()
+        .map(|(first, entity)| (first, entity.id))
+        .collect()
+}
+
+fn value<S: Storage>(schematic: &Schematic<S>, id: EntityId) -> &EntityValue {
+    &schematic.get_entity(&id).unwrap().value
+}
+
+fn ratios<S: Storage>(
+    schematic: &Schematic<S>,
+    adjacency: Adjacency,
+    gear_rule: &GearRule,
+) -> BTreeMap<Position, i128> {
+    gear_rule
+        .gears(schematic, adjacency)
+        .filter_map(|(gear, nums)| {
+            let first = schematic.get_entity_positions(&gear.id).next()?;
+            Some((first, gear_rule.ratio(&nums)))
+        })
+        .collect()
+}
+
+impl Diff {
+    pub fn json(&self) -> String {
+        let cell = |pos: &Position| format!("\"row\":{},\"col\":{}", pos.row(), pos.col());
+        let number = |value: Option<i128>| value.map_or("null".to_string(), |v| v.to_string());
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        let entities = |entities: &[(Position, EntityValue)]| {
+            list(
+                entities
+                    .iter()
+                    .map(|(pos, value)| {
+                        format!("{{{},\"value\":{}}}", cell(pos), json_string(value))
+                    })
+                    .collect(),
+            )
+        };
+        let numbers = |numbers: &[(Position, i128)]| {
+            list(
+                numbers
+                    .iter()
+                    .map(|(pos, num)| format!("{{{},\"value\":{num}}}", cell(pos)))
+                    .collect(),
+            )
+        };
+        let changed = list(
+            self.changed
+                .iter()
+                .map(|(pos, old, new)| {
+                    format!(
+                        "{{{},\"old\":{},\"new\":{}}}",
+                        cell(pos),
+                        json_string(old),
+                        json_string(new)
+                    )
+                })
+                .collect(),
+        );
+        let gears = list(
+            self.gears
+                .iter()
+                .map(|(pos, old, new)| {
+                    format!(
+                        "{{{},\"old\":{},\"new\":{}}}",
+                        cell(pos),
+                        number(*old),
+                        number(*new)
+                    )
+                })
+                .collect(),
+        );
+        let [old, new] = self.totals;
+        format!(
+            "{{\"part_1\":{{\"old\":{},\"new\":{}}},\"part_2\":{{\"old\":{},\"new\":{}}},\"added\":{},\"removed\":{},\"changed\":{},\"gained_part\":{},\"lost_part\":{},\"gears\":{}}}",
+            old.0,
+            new.0,
+            old.1,
+            new.1,
+            entities(&self.added),
+            entities(&self.removed),
+            changed,
+            numbers(&self.gained_part),
+            numbers(&self.lost_part),
+            gears
+        )
+    }
+}
+
+fn json_string(value: &EntityValue) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.to_string().chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [old, new] = self.totals;
+        writeln!(f, "part 1: {} -> {} ({:+})", old.0, new.0, new.0 - old.0)?;
+        writeln!(f, "part 2: {} -> {} ({:+})", old.1, new.1, new.1 - old.1)?;
+        let ratio = |ratio: Option<i128>| ratio.map_or("none".to_string(), |r| r.to_string());
+        for (pos, value) in &self.added {
+            writeln!(f, "added {} {value}", pos)?;
+        }
+        for (pos, value) in &self.removed {
+            writeln!(f, "removed {} {value}", pos)?;
+        }
+        for (pos, old, new) in &self.changed {
+            writeln!(f, "changed {} {old} -> {new}", pos)?;
+        }
+        for (pos, num) in &self.gained_part {
+            writeln!(f, "gained part {} {num}", pos)?;
+        }
+        for (pos, num) in &self.lost_part {
+            writeln!(f, "lost part {} {num}", pos)?;
+        }
+        for (pos, old, new) in &self.gears {
+            writeln!(f, "gear {} {} -> {}", pos, ratio(*old), ratio(*new))?;
+        }
+        Ok(())
+    }
+}
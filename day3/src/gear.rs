@@ -0,0 +1,156 @@
+use crate::{
+    schematic::{Adjacency, Entity, EntityId, EntityValue, Schematic},
+    storage::Storage,
+};
+use anyhow::{bail, ensure, Context};
+use std::{collections::HashMap, str::FromStr};
+
+/// Which symbols are gears and how their numbers combine into a ratio, written as
+/// `symbols=*,exactly=2,aggregate=product` (the default). `symbols=any` matches every symbol
+/// and `at-least=N` replaces `exactly=N`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GearRule {
+    /// `None` means any symbol.
+    pub symbols: Option<Vec<char>>,
+    pub count: NeighbourCount,
+    pub aggregate: Aggregate,
+}
+
+/// How many distinct numbers must touch a gear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighbourCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Default for GearRule {
+    fn default() -> Self {
+        Self {
+            symbols: Some(vec!['*']),
+            count: NeighbourCount::Exactly(2),
+            aggregate: Aggregate::Product,
+        }
+    }
+}
+
+impl GearRule {
+    /// Each gear with the numbers touching it, by id.
+    pub fn gears<'a, S: Storage>(
+        &'a self,
+        schematic: &'a Schematic<S>,
+        adjacency: Adjacency,
+    ) -> impl Iterator<Item = (&'a Entity, HashMap<EntityId, i128>)> + 'a {
+        schematic.entities().filter_map(move |entity| {
+            self.numbers(schematic, entity, adjacency)
+                .map(|nums| (entity, nums))
+        })
+    }
+
+    /// The numbers touching `entity` by id, if it is a gear.
+    pub fn numbers<S: Storage>(
+        &self,
+        schematic: &Schematic<S>,
+        entity: &Entity,
+        adjacency: Adjacency,
+    ) -> Option<HashMap<EntityId, i128>> {
+        match entity.value {
+            EntityValue::Symbol(symbol) if self.is_gear_symbol(symbol) => {}
+            _ => return None,
+        }
+        let nums = schematic
+            .get_entity_positions(&entity.id)
+            .flat_map(|pos| schematic.neighbours(&pos, adjacency))
+            .filter_map(|pos| match schematic.get_entity_at_position(&pos) {
+                Some(Entity {
+                    id,
+                    value: EntityValue::Number(num),
+                }) => Some((*id, *num)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        self.count.matches(nums.len()).then_some(nums)
+    }
+
+    pub fn is_gear_symbol(&self, symbol: char) -> bool {
+        self.symbols
+            .as_ref()
+            .is_none_or(|symbols| symbols.contains(&symbol))
+    }
+
+    pub fn ratio(&self, nums: &HashMap<EntityId, i128>) -> i128 {
+        self.aggregate.apply(nums.values().copied())
+    }
+}
+
+impl NeighbourCount {
+    pub fn matches(self, count: usize) -> bool {
+        match self {
+            NeighbourCount::Exactly(n) => count == n,
+            NeighbourCount::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+impl Aggregate {
+    /// Gears always have at least one number, so there is always a maximum.
+    pub fn apply(self, nums: impl Iterator<Item = i128>) -> i128 {
+        match self {
+            Aggregate::Product => nums.product(),
+            Aggregate::Sum => nums.sum(),
+            Aggregate::Max => nums.max().unwrap_or(0),
+        }
+    }
+}
+
+impl FromStr for GearRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rule = GearRule::default();
+        for entry in s.split(',') {
+            let (key, value) = entry
+                .split_once('=')
+                .with_context(|| format!("expected key=value in gear rule, got {entry:?}"))?;
+            match key.trim() {
+                "symbols" => {
+                    rule.symbols = match value {
+                        "any" => None,
+                        "" => bail!("gear rule needs at least one symbol"),
+                        _ => Some(value.chars().collect()),
+                    }
+                }
+                "exactly" | "at-least" => {
+                    let count: usize = value
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("invalid number of numbers {value:?}"))?;
+                    ensure!(count > 0, "gears need at least one number");
+                    rule.count = if key.trim() == "exactly" {
+                        NeighbourCount::Exactly(count)
+                    } else {
+                        NeighbourCount::AtLeast(count)
+                    };
+                }
+                "aggregate" => {
+                    rule.aggregate = match value.trim() {
+                        "product" => Aggregate::Product,
+                        "sum" => Aggregate::Sum,
+                        "max" => Aggregate::Max,
+                        _ => bail!("unknown aggregate {value}, expected product, sum or max"),
+                    }
+                }
+                key => bail!(
+                    "unknown gear rule key {key}, expected symbols, exactly, at-least or aggregate"
+                ),
+            }
+        }
+        Ok(rule)
+    }
+}
@@ -1,78 +1,290 @@
+use anyhow::{bail, ensure, Context};
 use std::{
-    collections::HashMap,
-    io::{stdin, BufRead},
+    fs::File,
+    io::{stdin, BufRead, BufReader},
+    path::PathBuf,
 };
+mod bench;
+mod component;
+mod diff;
+mod gear;
+mod render;
 mod schematic;
-use schematic::{Cell, Entity, EntityValue, Schematic};
+mod storage;
+mod stream;
+use component::components;
+use diff::{diff, DiffFormat};
+use gear::GearRule;
+use render::{render, RenderFormat};
+use schematic::{
+    check_numbers, Adjacency, CellRules, EntityId, EntityValue, Grid, Position, Schematic, Wrap,
+};
+use storage::{Backend, DenseStorage, HashStorage, Storage};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "bench").is_some() {
+        let size = match args.next() {
+            Some(size) => size.parse()?,
+            None => 2000,
+        };
+        bench::run(size)?;
+        return Ok(());
+    }
+    let options = parse_args(args)?;
+    if options.stream {
+        ensure!(
+            options.storage == Backend::default()
+                && options.wrap == Wrap::default()
+                && options.edits.is_empty()
+                && options.render.is_none()
+                && !options.components
+                && !options.entities
+                && options.diff.is_none(),
+            "--stream cannot be combined with --storage, --wrap, --set, --render, --components, \
+             --entities or --diff"
+        );
+        let (part_1, part_2) = stream::solve(
+            stdin().lock(),
+            &options.cells,
+            options.adjacency,
+            &options.gear,
+        )?;
+        println!("part 1: {part_1}");
+        println!("part 2: {part_2}");
+        return Ok(());
+    }
+    let grid = parse_grid(stdin().lock(), &options.cells);
+    check_grid(&grid, options.wrap)?;
+    match options.storage {
+        Backend::Hash => run(
+            Schematic::<HashStorage>::with_wrap(grid, options.wrap),
+            &options,
+        ),
+        Backend::Dense => run(
+            Schematic::<DenseStorage>::with_wrap(grid, options.wrap),
+            &options,
+        ),
+    }
+}
+
+fn run<S: Storage>(mut schematic: Schematic<S>, options: &Options) -> anyhow::Result<()> {
+    if !options.edits.is_empty() {
+        schematic.track_totals(options.adjacency, options.gear.clone());
+        for &(pos, c) in &options.edits {
+            ensure!(
+                schematic.get_cell(&pos).is_some(),
+                "--set {pos} is outside the schematic"
+            );
+            schematic
+                .set_char(pos, c, &options.cells)
+                .with_context(|| format!("--set {pos},{c}"))?;
+        }
+    }
+    if let Some(path) = &options.diff {
+        let file = File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+        let grid = parse_grid(BufReader::new(file), &options.cells);
+        check_grid(&grid, options.wrap).with_context(|| format!("in {}", path.display()))?;
+        let new = Schematic::<S>::with_wrap(grid, options.wrap);
+        let diff = diff(&schematic, &new, options.adjacency, &options.gear);
+        match options.diff_format {
+            DiffFormat::Text => print!("{diff}"),
+            DiffFormat::Json => println!("{}", diff.json()),
+        }
+        return Ok(());
+    }
+    if options.components {
+        print_components(&schematic, options.adjacency);
+        return Ok(());
+    }
+    if options.entities {
+        for (pos, entity) in schematic.entities_by_position() {
+            println!("{pos} {}", entity.value);
+        }
+        return Ok(());
+    }
+    if let Some(format) = options.render {
+        print!(
+            "{}",
+            render(&schematic, options.adjacency, &options.gear, format)
+        );
+        return Ok(());
+    }
+    let (part_1, part_2) = match schematic.totals() {
+        Some(totals) => (totals.part_number_sum, totals.gear_ratio_sum),
+        None => (
+            part_1(&schematic, options.adjacency),
+            part_2(&schematic, options.adjacency, &options.gear),
+        ),
+    };
+    println!("part 1: {part_1}");
+    println!("part 2: {part_2}");
+    Ok(())
+}
+
+fn print_components<S: Storage>(schematic: &Schematic<S>, adjacency: Adjacency) {
+    for (i, component) in (1..).zip(components(schematic, adjacency)) {
+        let (top_left, bottom_right) = (component.top_left, component.bottom_right);
+        println!(
+            "component {i}: size {}, rows {}-{}, cols {}-{}, part number sum {}",
+            component.entities.len(),
+            top_left.row(),
+            bottom_right.row(),
+            top_left.col(),
+            bottom_right.col(),
+            component.part_number_sum
+        );
+        let values: Vec<String> = component
+            .entities
+            .iter()
+            .filter_map(|id| schematic.get_entity(id))
+            .map(|entity| entity.value.to_string())
+            .collect();
+        println!("  {}", values.join(" "));
+    }
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    storage: Backend,
+    adjacency: Adjacency,
+    render: Option<RenderFormat>,
+    cells: CellRules,
+    gear: GearRule,
+    components: bool,
+    entities: bool,
+    wrap: Wrap,
+    /// A later revision to compare the input with.
+    diff: Option<PathBuf>,
+    diff_format: DiffFormat,
+    /// Solve row by row without building a schematic.
+    stream: bool,
+    /// Cells to overwrite after parsing, in order.
+    edits: Vec<(Position, char)>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--storage" => {
+                let storage = args.next().context("--storage expects hash or dense")?;
+                options.storage = storage.parse()?;
+            }
+            "--adjacency" => {
+                let adjacency = args
+                    .next()
+                    .context("--adjacency expects 8, 4, chebyshev:K, row or diagonal")?;
+                options.adjacency = adjacency.parse()?;
+            }
+            "--render" => {
+                let format = args.next().context("--render expects ansi or html")?;
+                options.render = Some(format.parse()?);
+            }
+            "--symbols" => {
+                let symbols = args
+                    .next()
+                    .context("--symbols expects the characters to treat as symbols")?;
+                options.cells.symbols = Some(symbols.chars().collect());
+            }
+            "--signed" => options.cells.signed = true,
+            "--components" => options.components = true,
+            "--entities" => options.entities = true,
+            "--stream" => options.stream = true,
+            "--diff" => {
+                let path = args
+                    .next()
+                    .context("--diff expects the path of a new revision")?;
+                options.diff = Some(path.into());
+            }
+            "--diff-format" => {
+                let format = args.next().context("--diff-format expects text or json")?;
+                options.diff_format = format.parse()?;
+            }
+            "--gear" => {
+                let rule = args
+                    .next()
+                    .context("--gear expects a rule like symbols=*,exactly=2,aggregate=product")?;
+                options.gear = rule.parse()?;
+            }
+            "--wrap" => {
+                let wrap = args
+                    .next()
+                    .context("--wrap expects none, rows, cols or both")?;
+                options.wrap = wrap.parse()?;
+            }
+            "--set" => {
+                let edit = args.next().context("--set expects ROW,COL,CHAR")?;
+                options.edits.push(parse_edit(&edit)?);
+            }
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_edit(edit: &str) -> anyhow::Result<(Position, char)> {
+    let mut fields = edit.splitn(3, ',');
+    let (Some(row), Some(col), Some(c)) = (fields.next(), fields.next(), fields.next()) else {
+        bail!("--set expects ROW,COL,CHAR, got {edit:?}");
+    };
+    let mut chars = c.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        bail!("--set expects a single character, got {c:?}");
+    };
+    let row = row
+        .parse()
+        .with_context(|| format!("invalid row {row:?}"))?;
+    let col = col
+        .parse()
+        .with_context(|| format!("invalid column {col:?}"))?;
+    Ok(((row, col).into(), c))
+}
 
-fn main() {
-    let schematic = parse_input(stdin().lock());
-    println!("part 1: {}", part_1(&schematic));
-    println!("part 2: {}", part_2(&schematic));
+fn check_grid(grid: &Grid, wrap: Wrap) -> anyhow::Result<()> {
+    for (line, row) in (1..).zip(grid) {
+        check_numbers(row, wrap.cols).with_context(|| format!("line {line}"))?;
+    }
+    Ok(())
 }
 
-fn parse_input(input: impl BufRead) -> Schematic {
-    let grid: Vec<Vec<Cell>> = input
+fn parse_grid(input: impl BufRead, rules: &CellRules) -> Grid {
+    input
         .lines()
-        .map(|line| line.unwrap())
-        .map(|line| {
-            line.chars()
-                .map(|c| match c {
-                    '.' => Cell::Empty,
-                    c if c.is_ascii_digit() => Cell::Digit(c),
-                    c if !c.is_ascii_alphanumeric() => Cell::Symbol(c),
-                    _ => panic!("unexpected cell value {c}"),
-                })
-                .collect()
-        })
-        .collect();
-    Schematic::from(grid)
+        .map(|line| rules.row(&line.unwrap()))
+        .collect()
 }
 
-fn part_1(schematic: &Schematic) -> u64 {
+fn part_1<S: Storage>(schematic: &Schematic<S>, adjacency: Adjacency) -> i128 {
     schematic
-        .get_entities()
-        .values()
+        .entities()
         .filter_map(|entity| match &entity.value {
             EntityValue::Number(num) => Some((entity.id, num)),
             _ => None,
         })
-        .filter(|(entity_id, _)| {
-            schematic
-                .get_entity_positions(&entity_id)
-                .flat_map(|pos| pos.adjacent())
-                .any(|pos| match schematic.get_entity_at_position(&pos) {
-                    Some(entity) if entity.is_symbol() => true,
-                    _ => false,
-                })
-        })
+        .filter(|(entity_id, _)| is_part_number(schematic, entity_id, adjacency))
         .map(|(_, num)| num)
         .sum()
 }
 
-fn part_2(schematic: &Schematic) -> u64 {
+fn is_part_number<S: Storage>(
+    schematic: &Schematic<S>,
+    entity_id: &EntityId,
+    adjacency: Adjacency,
+) -> bool {
     schematic
-        .get_entities()
-        .values()
-        .filter(|entity| match &entity.value {
-            EntityValue::Symbol('*') => true,
-            _ => false,
-        })
-        .map(|star| {
+        .get_entity_positions(entity_id)
+        .flat_map(|pos| schematic.neighbours(&pos, adjacency))
+        .any(|pos| {
             schematic
-                .get_entity_positions(&star.id)
-                .flat_map(|pos| pos.adjacent())
-                .filter_map(|pos| match schematic.get_entity_at_position(&pos) {
-                    Some(Entity {
-                        id,
-                        value: EntityValue::Number(num),
-                    }) => Some((id, *num)),
-                    _ => None,
-                })
-                .collect::<HashMap<_, _>>()
+                .get_entity_at_position(&pos)
+                .is_some_and(|entity| entity.is_symbol())
         })
-        .filter(|nums| nums.len() == 2)
-        .map(|nums| nums.values().product::<u64>())
+}
+
+fn part_2<S: Storage>(schematic: &Schematic<S>, adjacency: Adjacency, rule: &GearRule) -> i128 {
+    rule.gears(schematic, adjacency)
+        .map(|(_, nums)| rule.ratio(&nums))
         .sum()
 }
 
@@ -81,15 +293,20 @@ mod tests {
     use std::collections::HashSet;
 
     use super::*;
+    use component::Component;
+    use schematic::Cell;
     const EXAMPLE: &str = include_str!("../example.txt");
 
+    fn parse_input(input: impl BufRead) -> Schematic {
+        Schematic::from(parse_grid(input, &CellRules::default()))
+    }
+
     #[test]
     fn test_parse_input_example() {
         let schematic = parse_input(EXAMPLE.as_bytes());
         assert_eq!(
             schematic
-                .get_entities()
-                .values()
+                .entities()
                 .filter_map(|entity| match entity.value {
                     EntityValue::Number(number) => Some(number),
                     _ => None,
@@ -123,17 +340,581 @@ mod tests {
         );
 
         assert_eq!(schematic.get_entity_at_position(&(0, 3).into()), None);
+
+        let id = schematic.get_entity_at_position(&(0, 1).into()).unwrap().id;
+        assert_eq!(
+            schematic.get_entity_positions(&id).collect::<Vec<_>>(),
+            [(0, 0).into(), (0, 1).into(), (0, 2).into()]
+        );
+        assert_eq!(schematic.get_entity_positions(&0).count(), 0);
     }
 
     #[test]
     fn test_part_1_example() {
         let schematic = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_1(&schematic), 4361);
+        assert_eq!(part_1(&schematic, Adjacency::default()), 4361);
     }
 
     #[test]
     fn test_part_2_example() {
         let schematic = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_2(&schematic), 467835);
+        assert_eq!(
+            part_2(&schematic, Adjacency::default(), &GearRule::default()),
+            467835
+        );
+    }
+
+    #[test]
+    fn test_adjacency_rules_example() {
+        let schematic = parse_input(EXAMPLE.as_bytes());
+        let parts = |adjacency| {
+            (
+                part_1(&schematic, adjacency),
+                part_2(&schematic, adjacency, &GearRule::default()),
+            )
+        };
+        assert_eq!(parts(Adjacency::Eight), (4361, 467835));
+        assert_eq!(parts(Adjacency::Chebyshev(1)), (4361, 467835));
+        assert_eq!(parts(Adjacency::Four), (2547, 0));
+        assert_eq!(parts(Adjacency::SameRow), (617, 0));
+        assert_eq!(parts(Adjacency::Diagonal), (3744, 467835));
+        assert_eq!(parts(Adjacency::Chebyshev(2)), (4533, 0));
+        assert_eq!(parts(Adjacency::Chebyshev(0)), (0, 0));
+    }
+
+    #[test]
+    fn test_gear_rules_example() {
+        let schematic = parse_input(EXAMPLE.as_bytes());
+        let part_2_with =
+            |rule: &str| part_2(&schematic, Adjacency::default(), &rule.parse().unwrap());
+        assert_eq!(part_2_with("symbols=*,exactly=2,aggregate=product"), 467835);
+        assert_eq!(part_2_with("aggregate=sum"), 467 + 35 + 755 + 598);
+        assert_eq!(
+            part_2_with("symbols=any,exactly=1,aggregate=max"),
+            633 + 617 + 592 + 664
+        );
+        assert_eq!(part_2_with("symbols=any,at-least=1,aggregate=sum"), 4361);
+        assert_eq!(part_2_with("symbols=#+$,at-least=1"), 633 + 592 + 664);
+        assert_eq!(part_2_with("at-least=3"), 0);
+
+        for invalid in [
+            "exactly=0",
+            "symbols=",
+            "aggregate=min",
+            "ratio=2",
+            "exactly",
+        ] {
+            assert!(invalid.parse::<GearRule>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_components_example() {
+        let schematic = parse_input(EXAMPLE.as_bytes());
+        let found = components(&schematic, Adjacency::default());
+        let values = |component: &Component| -> Vec<String> {
+            component
+                .entities
+                .iter()
+                .map(|id| schematic.get_entity(id).unwrap().value.to_string())
+                .collect()
+        };
+        assert_eq!(found.len(), 8);
+        assert_eq!(values(&found[0]), ["467", "*", "35"]);
+        assert_eq!(found[0].top_left, (0, 0).into());
+        assert_eq!(found[0].bottom_right, (2, 3).into());
+        assert_eq!(found[0].part_number_sum, 467 + 35);
+        assert_eq!(values(&found[1]), ["755", "*", "598"]);
+        assert_eq!(values(&found[7]), ["58"]);
+        assert_eq!(found[7].part_number_sum, 0);
+        assert_eq!(
+            found.iter().map(|c| c.part_number_sum).sum::<i128>(),
+            part_1(&schematic, Adjacency::default())
+        );
+
+        let chain = parse_input("1*2.\n...#\n..3.\n9...\n".as_bytes());
+        let found = components(&chain, Adjacency::default());
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].entities.len(), 5);
+        assert_eq!(found[0].bottom_right, (2, 3).into());
+        assert_eq!(found[0].part_number_sum, 6);
+        let found = components(&chain, Adjacency::Four);
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[0].entities.len(), 3);
+        assert_eq!(found[0].part_number_sum, 3);
+    }
+
+    #[test]
+    fn test_render_example() {
+        let schematic = parse_input(EXAMPLE.as_bytes());
+        let ansi = render(
+            &schematic,
+            Adjacency::default(),
+            &GearRule::default(),
+            RenderFormat::Ansi,
+        );
+        let lines: Vec<&str> = ansi.lines().collect();
+        assert_eq!(lines[0], "\x1b[1;30;43m467\x1b[0m..\x1b[31m114\x1b[0m..");
+        assert_eq!(lines[4], "\x1b[32m617\x1b[0m\x1b[33m*\x1b[0m......");
+        assert_eq!(lines[8], "...\x1b[33m$\x1b[0m.\x1b[1;30;44m*\x1b[0m....");
+        let mut plain = String::new();
+        for (i, part) in ansi.split('\x1b').enumerate() {
+            plain.push_str(if i == 0 {
+                part
+            } else {
+                &part[part.find('m').unwrap() + 1..]
+            });
+        }
+        assert_eq!(plain, EXAMPLE);
+
+        let html = render(
+            &schematic,
+            Adjacency::default(),
+            &GearRule::default(),
+            RenderFormat::Html,
+        );
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(
+            "\n<span class=\"gear gear-0\">467</span>..<span class=\"non-part\">114</span>..\n"
+        ));
+        assert!(html.contains(
+            ".<span class=\"part\">664</span>.<span class=\"gear gear-1\">598</span>..\n"
+        ));
+
+        let schematic = parse_input("1<2&\n".as_bytes());
+        let html = render(
+            &schematic,
+            Adjacency::default(),
+            &GearRule::default(),
+            RenderFormat::Html,
+        );
+        assert!(html.contains("<span class=\"part\">1</span><span class=\"symbol\">&lt;</span>"));
+    }
+
+    #[test]
+    fn test_identifiers_and_signs() {
+        let input = "R2D2.-5...\n..*..x-3..\n-12*4.a-1.\n";
+        let parts = |rules: CellRules| {
+            let schematic: Schematic = Schematic::from(parse_grid(input.as_bytes(), &rules));
+            let adjacency = Adjacency::default();
+            (
+                part_1(&schematic, adjacency),
+                part_2(&schematic, adjacency, &GearRule::default()),
+            )
+        };
+        let stars = || Some(vec!['*']);
+        assert_eq!(parts(CellRules::default()), (5 + 3 + 12 + 4 + 1, 12 * 4));
+        let signed = CellRules {
+            signed: true,
+            ..CellRules::default()
+        };
+        assert_eq!(parts(signed.clone()), (-5 + 3 - 12 + 4 + 1, -12 * 4));
+        let only_stars = CellRules {
+            symbols: stars(),
+            ..CellRules::default()
+        };
+        assert_eq!(parts(only_stars), (12 + 4, 12 * 4));
+        let signed_stars = CellRules {
+            symbols: stars(),
+            ..signed.clone()
+        };
+        assert_eq!(parts(signed_stars), (-12 + 4, -12 * 4));
+
+        let schematic: Schematic = Schematic::from(parse_grid(input.as_bytes(), &signed));
+        let value_at = |pos: (i32, i32)| {
+            schematic
+                .get_entity_at_position(&pos.into())
+                .map(|entity| &entity.value)
+        };
+        let r2d2 = EntityValue::Identifier("R2D2".to_string());
+        assert_eq!(value_at((0, 0)), Some(&r2d2));
+        assert_eq!(value_at((0, 3)), Some(&r2d2));
+        assert_eq!(value_at((0, 5)), Some(&EntityValue::Number(-5)));
+        assert_eq!(value_at((1, 6)), Some(&EntityValue::Symbol('-')));
+        assert_eq!(value_at((2, 0)), Some(&EntityValue::Number(-12)));
+        assert_eq!(value_at((2, 7)), Some(&EntityValue::Symbol('-')));
+        assert_eq!(value_at((2, 8)), Some(&EntityValue::Number(1)));
+    }
+
+    #[test]
+    fn test_dense_storage_example() {
+        let schematic =
+            Schematic::<DenseStorage>::from(parse_grid(EXAMPLE.as_bytes(), &CellRules::default()));
+        assert_eq!(part_1(&schematic, Adjacency::default()), 4361);
+        assert_eq!(
+            part_2(&schematic, Adjacency::default(), &GearRule::default()),
+            467835
+        );
+        assert_eq!(
+            schematic
+                .get_entity_at_position(&(9, 7).into())
+                .map(|entity| &entity.value),
+            Some(&EntityValue::Number(598))
+        );
+        for pos in [(-1, 0), (0, -1), (0, 10), (10, 0), (0, 3)] {
+            assert_eq!(schematic.get_entity_at_position(&pos.into()), None);
+        }
+
+        let listing = |entities: Vec<(Position, &schematic::Entity)>| -> Vec<String> {
+            entities
+                .into_iter()
+                .map(|(pos, entity)| format!("{pos} {}", entity.value))
+                .collect()
+        };
+        let hashed = parse_input(EXAMPLE.as_bytes());
+        let listed = listing(schematic.entities_by_position());
+        assert_eq!(listed, listing(hashed.entities_by_position()));
+        assert_eq!(listed[..4], ["0,0 467", "0,5 114", "1,3 *", "2,2 35"]);
+    }
+
+    #[test]
+    fn test_wrap_example() {
+        let input = "3.#...12\n......*.\n.4.....5\n";
+        let build = |wrap: &str| -> Schematic {
+            let grid = parse_grid(input.as_bytes(), &CellRules::default());
+            Schematic::with_wrap(grid, wrap.parse().unwrap())
+        };
+        let parts = |wrap| {
+            let schematic = build(wrap);
+            let adjacency = Adjacency::default();
+            (
+                part_1(&schematic, adjacency),
+                part_2(&schematic, adjacency, &GearRule::default()),
+            )
+        };
+        assert_eq!(parts("none"), (12 + 5, 12 * 5));
+        // 12 continues into the 3 at the start of its row.
+        assert_eq!(parts("cols"), (123 + 5, 123 * 5));
+        // The 4 on the last row touches the # on the first.
+        assert_eq!(parts("rows"), (12 + 5 + 4, 12 * 5));
+        assert_eq!(parts("both"), (123 + 5 + 4, 123 * 5));
+
+        let schematic = build("cols");
+        let id = schematic.get_entity_at_position(&(0, 0).into()).unwrap().id;
+        assert_eq!(
+            schematic.get_entity(&id).unwrap().value,
+            EntityValue::Number(123)
+        );
+        assert_eq!(
+            schematic.get_entity_positions(&id).collect::<Vec<_>>(),
+            [(0, 6).into(), (0, 7).into(), (0, 0).into()]
+        );
+        assert_eq!(
+            schematic
+                .neighbours(&(0, 0).into(), Adjacency::Four)
+                .collect::<Vec<_>>(),
+            [(0, 7).into(), (0, 1).into(), (1, 0).into()]
+        );
+        // A radius past the size of the grid reaches every other cell once.
+        let schematic = build("both");
+        let mut reached: Vec<_> = schematic
+            .neighbours(&(1, 3).into(), Adjacency::Chebyshev(60_000))
+            .collect();
+        assert_eq!(reached.len(), 3 * 8 - 1);
+        reached.sort();
+        reached.dedup();
+        assert_eq!(reached.len(), 3 * 8 - 1);
+        assert!(!reached.contains(&(1, 3).into()));
+        let far = |wrap: &str| {
+            let schematic = Schematic::<HashStorage>::with_wrap(
+                parse_grid("1*\n".as_bytes(), &CellRules::default()),
+                wrap.parse().unwrap(),
+            );
+            part_1(&schematic, Adjacency::Chebyshev(60_000))
+        };
+        assert_eq!(far("both"), 1);
+        assert_eq!(far("none"), 1);
+
+        // Without an empty cell, symbol or sign a wrapping row reads from its first column.
+        let grid = vec![CellRules::default().row("12ab")];
+        let schematic: Schematic = Schematic::with_wrap(grid, "cols".parse().unwrap());
+        let values: HashSet<String> = schematic
+            .entities()
+            .map(|entity| entity.value.to_string())
+            .collect();
+        assert_eq!(values, ["12".to_string(), "ab".to_string()].into());
+        assert!("sideways".parse::<Wrap>().is_err());
+    }
+
+    #[test]
+    fn test_stream_matches_in_memory() {
+        let signed = CellRules {
+            signed: true,
+            ..CellRules::default()
+        };
+        let inputs = [
+            (EXAMPLE.to_string(), CellRules::default()),
+            ("R2D2.-5...\n..*..x-3..\n-12*4.a-1.\n".to_string(), signed),
+            ("1*\n".to_string(), CellRules::default()),
+            (String::new(), CellRules::default()),
+            (bench::generate(40), CellRules::default()),
+            (bench::generate(97), CellRules::default()),
+        ];
+        let rules: Vec<GearRule> = [
+            "symbols=*,exactly=2,aggregate=product",
+            "symbols=any,at-least=1,aggregate=sum",
+            "symbols=#$,exactly=1,aggregate=max",
+        ]
+        .iter()
+        .map(|rule| rule.parse().unwrap())
+        .collect();
+        for (input, cells) in &inputs {
+            let schematic: Schematic = Schematic::from(parse_grid(input.as_bytes(), cells));
+            for adjacency in [
+                Adjacency::Eight,
+                Adjacency::Four,
+                Adjacency::SameRow,
+                Adjacency::Diagonal,
+                Adjacency::Chebyshev(0),
+                Adjacency::Chebyshev(3),
+            ] {
+                for rule in &rules {
+                    assert_eq!(
+                        stream::solve(input.as_bytes(), cells, adjacency, rule).unwrap(),
+                        (
+                            part_1(&schematic, adjacency),
+                            part_2(&schematic, adjacency, rule)
+                        ),
+                        "{adjacency:?} {rule:?} on {input:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_example() {
+        let old = parse_input(EXAMPLE.as_bytes());
+        // A new symbol makes 114 a part, removing a star drops 617, and a third number next to
+        // the second gear stops it being one.
+        let revised = EXAMPLE
+            .replacen("467..114..", "467.#114..", 1)
+            .replacen("617*......", "617.......", 1)
+            .replacen(".664.598..", ".664.59...", 1)
+            .replacen("...$.*....", "...$.*1...", 1);
+        let new = parse_input(revised.as_bytes());
+        let found = diff(&old, &new, Adjacency::default(), &GearRule::default());
+
+        let number = |num| EntityValue::Number(num);
+        assert_eq!(
+            found.totals,
+            [(4361, 467835), (4361 + 114 - 617 - 598 + 59 + 1, 16345)]
+        );
+        assert_eq!(
+            found.added,
+            [
+                ((0, 4).into(), EntityValue::Symbol('#')),
+                ((8, 6).into(), number(1))
+            ]
+        );
+        assert_eq!(found.removed, [((4, 3).into(), EntityValue::Symbol('*'))]);
+        assert_eq!(found.changed, [((9, 5).into(), number(598), number(59))]);
+        assert_eq!(found.gained_part, [((0, 5).into(), 114)]);
+        assert_eq!(found.lost_part, [((4, 0).into(), 617)]);
+        assert_eq!(found.gears, [((8, 5).into(), Some(755 * 598), None)]);
+        assert_eq!(
+            found.to_string().lines().collect::<Vec<_>>(),
+            [
+                "part 1: 4361 -> 3320 (-1041)",
+                "part 2: 467835 -> 16345 (-451490)",
+                "added 0,4 #",
+                "added 8,6 1",
+                "removed 4,3 *",
+                "changed 9,5 598 -> 59",
+                "gained part 0,5 114",
+                "lost part 4,0 617",
+                "gear 8,5 451490 -> none",
+            ]
+        );
+        assert_eq!(
+            found.json(),
+            concat!(
+                "{\"part_1\":{\"old\":4361,\"new\":3320},\"part_2\":{\"old\":467835,\"new\":16345},",
+                "\"added\":[{\"row\":0,\"col\":4,\"value\":\"#\"},{\"row\":8,\"col\":6,\"value\":\"1\"}],",
+                "\"removed\":[{\"row\":4,\"col\":3,\"value\":\"*\"}],",
+                "\"changed\":[{\"row\":9,\"col\":5,\"old\":\"598\",\"new\":\"59\"}],",
+                "\"gained_part\":[{\"row\":0,\"col\":5,\"value\":114}],",
+                "\"lost_part\":[{\"row\":4,\"col\":0,\"value\":617}],",
+                "\"gears\":[{\"row\":8,\"col\":5,\"old\":451490,\"new\":null}]}"
+            )
+        );
+
+        let same = diff(&old, &old, Adjacency::default(), &GearRule::default());
+        assert!(same.added.is_empty() && same.changed.is_empty() && same.gears.is_empty());
+    }
+
+    #[test]
+    fn test_set_cell_example() {
+        let mut schematic = parse_input(EXAMPLE.as_bytes());
+        schematic.track_totals(Adjacency::default(), GearRule::default());
+        let value_at = |schematic: &Schematic, pos: (i32, i32)| {
+            schematic
+                .get_entity_at_position(&pos.into())
+                .map(|entity| entity.value.to_string())
+        };
+
+        // 467..114.. becomes 467.3114.., and then 46773114..
+        schematic.set_cell((0, 4).into(), Cell::Digit('3'));
+        assert_eq!(value_at(&schematic, (0, 4)).as_deref(), Some("3114"));
+        schematic.set_cell((0, 3).into(), Cell::Digit('7'));
+        assert_eq!(value_at(&schematic, (0, 0)).as_deref(), Some("46773114"));
+        let totals = schematic.totals().unwrap();
+        assert_eq!(totals.part_number_sum, 4361 - 467 + 46773114);
+        assert_eq!(totals.gear_ratio_sum, 467835 - 467 * 35 + 46773114 * 35);
+
+        // Splitting 633 by its middle digit leaves 6, still next to the #, and 3, which is not.
+        schematic.set_cell((2, 7).into(), Cell::Empty('.'));
+        assert_eq!(value_at(&schematic, (2, 6)).as_deref(), Some("6"));
+        assert_eq!(value_at(&schematic, (2, 8)).as_deref(), Some("3"));
+        let totals = schematic.totals().unwrap();
+        assert_eq!(totals.part_number_sum, 4361 - 467 + 46773114 - 633 + 6);
+
+        // Removing the second gear's star drops its ratio and 755 and 598 from part 1.
+        schematic.set_cell((8, 5).into(), Cell::Empty('.'));
+        let totals = schematic.totals().unwrap();
+        assert_eq!(totals.gear_ratio_sum, 46773114 * 35);
+        assert_eq!(
+            totals.part_number_sum,
+            4361 - 467 + 46773114 - 633 + 6 - 755 - 598
+        );
+
+        // A `-` written after a letter is a symbol, as it is when `a-1` is parsed.
+        let signed = CellRules {
+            signed: true,
+            ..CellRules::default()
+        };
+        let mut schematic: Schematic = Schematic::from(vec![signed.row("a.1")]);
+        schematic.set_char((0, 1).into(), '-', &signed).unwrap();
+        let values: Vec<String> = schematic
+            .entities_by_position()
+            .into_iter()
+            .map(|(_, entity)| entity.value.to_string())
+            .collect();
+        assert_eq!(values, ["a", "-", "1"]);
+        let mut schematic: Schematic = Schematic::from(vec![signed.row(&"9".repeat(39))]);
+        schematic.set_char((0, 1).into(), '.', &signed).unwrap();
+        assert!(schematic.set_char((0, 1).into(), '9', &signed).is_err());
+        assert_eq!(schematic.get_cell(&(0, 1).into()), Some(Cell::Empty('.')));
+
+        let options = parse_args(["--set", "0,4,x"].map(String::from).into_iter()).unwrap();
+        assert_eq!(options.edits, [((0, 4).into(), 'x')]);
+        for invalid in ["0,4", "0,4,xy", "a,4,x", "0,4,"] {
+            let args = ["--set", invalid].map(String::from).into_iter();
+            assert!(parse_args(args).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_set_cell_matches_rebuild() {
+        // Edits either place arbitrary cells, or write characters that the row is re-read with.
+        fn check<S: Storage>(seed: u64, by_char: bool) {
+            let mut state = seed;
+            let mut random = |n: usize| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % n as u64) as usize
+            };
+            let (rows, cols) = (5, 7);
+            let rules = CellRules {
+                signed: seed % 7 < 4,
+                symbols: (seed % 11 < 3).then(|| vec!['*', '#']),
+            };
+            let read = |lines: &[Vec<char>]| -> Grid {
+                lines
+                    .iter()
+                    .map(|line| rules.row(&line.iter().collect::<String>()))
+                    .collect()
+            };
+            let mut lines: Vec<Vec<char>> = (0..rows)
+                .map(|_| (0..cols).map(|_| random_char(&mut random)).collect())
+                .collect();
+            let mut grid = read(&lines);
+            let adjacency = [
+                Adjacency::Eight,
+                Adjacency::Four,
+                Adjacency::SameRow,
+                Adjacency::Diagonal,
+                Adjacency::Chebyshev(2),
+            ][seed as usize % 5];
+            let rule: GearRule = [
+                "symbols=*,exactly=2,aggregate=product",
+                "symbols=any,at-least=1,aggregate=sum",
+                "symbols=#,exactly=1,aggregate=max",
+            ][seed as usize % 3]
+                .parse()
+                .unwrap();
+
+            let wrap = ["none", "rows", "cols", "both"][seed as usize % 4]
+                .parse()
+                .unwrap();
+
+            let mut schematic = Schematic::<S>::with_wrap(grid.clone(), wrap);
+            schematic.track_totals(adjacency, rule.clone());
+            for _ in 0..40 {
+                let pos: Position = (random(rows) as i32, random(cols) as i32).into();
+                let (row, col) = (pos.row() as usize, pos.col() as usize);
+                if by_char {
+                    let c = random_char(&mut random);
+                    schematic.set_char(pos, c, &rules).unwrap();
+                    lines[row][col] = c;
+                    grid = read(&lines);
+                } else {
+                    let cell = random_cell(&mut random);
+                    schematic.set_cell(pos, cell);
+                    grid[row][col] = cell;
+                }
+
+                let rebuilt = Schematic::<S>::with_wrap(grid.clone(), wrap);
+                assert_eq!(entity_cells(&schematic), entity_cells(&rebuilt), "{seed}");
+                for row in 0..rows as i32 {
+                    for col in 0..cols as i32 {
+                        let pos = Position::new(row, col);
+                        assert_eq!(schematic.get_cell(&pos), rebuilt.get_cell(&pos));
+                    }
+                }
+                let totals = schematic.totals().unwrap();
+                assert_eq!(totals.part_number_sum, part_1(&rebuilt, adjacency));
+                assert_eq!(totals.gear_ratio_sum, part_2(&rebuilt, adjacency, &rule));
+            }
+        }
+
+        fn random_char(random: &mut impl FnMut(usize) -> usize) -> char {
+            match random(9) {
+                0..=2 => '.',
+                3..=5 => char::from(b'0' + random(10) as u8),
+                6 => ['a', 'b'][random(2)],
+                7 => '-',
+                _ => ['*', '#'][random(2)],
+            }
+        }
+
+        fn random_cell(random: &mut impl FnMut(usize) -> usize) -> Cell {
+            match random(9) {
+                0..=2 => Cell::Empty('.'),
+                3..=5 => Cell::Digit(char::from(b'0' + random(10) as u8)),
+                6 => Cell::Letter(['a', 'b'][random(2)]),
+                7 => Cell::Sign('-'),
+                _ => Cell::Symbol(['*', '#'][random(2)]),
+            }
+        }
+
+        fn entity_cells<S: Storage>(schematic: &Schematic<S>) -> Vec<(Vec<Position>, String)> {
+            schematic
+                .entities_by_position()
+                .into_iter()
+                .map(|(_, entity)| {
+                    let positions = schematic.get_entity_positions(&entity.id).collect();
+                    (positions, entity.value.to_string())
+                })
+                .collect()
+        }
+
+        for seed in 1..=60 {
+            for by_char in [false, true] {
+                check::<HashStorage>(seed, by_char);
+                check::<DenseStorage>(seed, by_char);
+            }
+        }
     }
 }
@@ -0,0 +1,209 @@
+use crate::{
+    gear::GearRule,
+    is_part_number,
+    schematic::{Adjacency, EntityValue, Position, Schematic},
+    storage::Storage,
+};
+use anyhow::bail;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderFormat {
+    Ansi,
+    Html,
+}
+
+impl FromStr for RenderFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ansi" => Ok(Self::Ansi),
+            "html" => Ok(Self::Html),
+            _ => bail!("unknown render format {s}, expected ansi or html"),
+        }
+    }
+}
+
+/// How a cell is highlighted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+    Empty,
+    Part,
+    NonPart,
+    Symbol,
+    Identifier,
+    /// A gear and its numbers, with gears numbered in row-major order.
+    Gear(usize),
+}
+
+const GEAR_COLORS: usize = 4;
+
+impl Style {
+    fn ansi(self) -> Option<String> {
+        match self {
+            Style::Empty => None,
+            Style::Part => Some("32".to_string()),
+            Style::NonPart => Some("31".to_string()),
+            Style::Symbol => Some("33".to_string()),
+            Style::Identifier => Some("36".to_string()),
+            Style::Gear(gear) => Some(format!("1;30;{}", 43 + gear % GEAR_COLORS)),
+        }
+    }
+
+    fn html_class(self) -> Option<String> {
+        match self {
+            Style::Empty => None,
+            Style::Part => Some("part".to_string()),
+            Style::NonPart => Some("non-part".to_string()),
+            Style::Symbol => Some("symbol".to_string()),
+            Style::Identifier => Some("identifier".to_string()),
+            Style::Gear(gear) => Some(format!("gear gear-{}", gear % GEAR_COLORS)),
+        }
+    }
+}
+
+const HTML_STYLE: &str = "\
+.schematic { background: #111; color: #666; }
+.part { color: #4c4; }
+.non-part { color: #e44; }
+.symbol { color: #dd4; }
+.identifier { color: #4dd; }
+.gear { color: #000; font-weight: bold; }
+.gear-0 { background: #dd4; }
+.gear-1 { background: #48f; }
+.gear-2 { background: #d4d; }
+.gear-3 { background: #4dd; }
+";
+
+/// The schematic with part numbers, other numbers, symbols, identifiers and each gear with
+/// its numbers highlighted, as ANSI escapes for a terminal or as an HTML page.
+pub fn render<S: Storage>(
+    schematic: &Schematic<S>,
+    adjacency: Adjacency,
+    gear_rule: &GearRule,
+    format: RenderFormat,
+) -> String {
+    let rows = styled_rows(schematic, adjacency, gear_rule);
+    match format {
+        RenderFormat::Ansi => {
+            let mut out = String::new();
+            for row in &rows {
+                for (style, text) in runs(row) {
+                    match style.ansi() {
+                        Some(code) => out.push_str(&format!("\x1b[{code}m{text}\x1b[0m")),
+                        None => out.push_str(&text),
+                    }
+                }
+                out.push('\n');
+            }
+            out
+        }
+        RenderFormat::Html => {
+            let mut out = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n{HTML_STYLE}</style>\n</head>\n<body>\n<pre class=\"schematic\">\n"
+            );
+            for row in &rows {
+                for (style, text) in runs(row) {
+                    let text = escape_html(&text);
+                    match style.html_class() {
+                        Some(class) => {
+                            out.push_str(&format!("<span class=\"{class}\">{text}</span>"))
+                        }
+                        None => out.push_str(&text),
+                    }
+                }
+                out.push('\n');
+            }
+            out.push_str("</pre>\n</body>\n</html>\n");
+            out
+        }
+    }
+}
+
+fn styled_rows<S: Storage>(
+    schematic: &Schematic<S>,
+    adjacency: Adjacency,
+    gear_rule: &GearRule,
+) -> Vec<Vec<(Style, char)>> {
+    let (rows, cols) = schematic.dimensions();
+    let mut cells = vec![vec![(Style::Empty, '.'); cols]; rows];
+
+    for entity in schematic.entities() {
+        let positions: Vec<_> = schematic.get_entity_positions(&entity.id).collect();
+        match entity.value {
+            EntityValue::Number(number) => {
+                let style = if is_part_number(schematic, &entity.id, adjacency) {
+                    Style::Part
+                } else {
+                    Style::NonPart
+                };
+                // Leading zeros are not kept in the value, but they still cover cells.
+                let digits = format!("{number:0width$}", width = positions.len());
+                for (pos, digit) in positions.into_iter().zip(digits.chars()) {
+                    *cell(&mut cells, pos) = (style, digit);
+                }
+            }
+            EntityValue::Identifier(ref identifier) => {
+                for (pos, c) in positions.into_iter().zip(identifier.chars()) {
+                    *cell(&mut cells, pos) = (Style::Identifier, c);
+                }
+            }
+            EntityValue::Symbol(symbol) => {
+                for pos in positions {
+                    *cell(&mut cells, pos) = (Style::Symbol, symbol);
+                }
+            }
+        }
+    }
+
+    // Number gears by position so their colors do not depend on storage order.
+    let mut found: Vec<_> = gear_rule
+        .gears(schematic, adjacency)
+        .map(|(star, numbers)| {
+            let mut ids: Vec<_> = numbers.into_keys().collect();
+            ids.push(star.id);
+            let first = schematic.get_entity_positions(&star.id).next();
+            (first, ids)
+        })
+        .collect();
+    found.sort_by_key(|(first, _)| *first);
+    for (gear, (_, ids)) in found.into_iter().enumerate() {
+        for id in ids {
+            for pos in schematic.get_entity_positions(&id) {
+                cell(&mut cells, pos).0 = Style::Gear(gear);
+            }
+        }
+    }
+    cells
+}
+
+fn cell(cells: &mut [Vec<(Style, char)>], pos: Position) -> &mut (Style, char) {
+    &mut cells[pos.row() as usize][pos.col() as usize]
+}
+
+/// Consecutive cells with the same style.
+fn runs(row: &[(Style, char)]) -> Vec<(Style, String)> {
+    let mut runs: Vec<(Style, String)> = Vec::new();
+    for (style, c) in row {
+        match runs.last_mut() {
+            Some((last, text)) if last == style => text.push(*c),
+            _ => runs.push((*style, c.to_string())),
+        }
+    }
+    runs
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
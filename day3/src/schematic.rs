@@ -1,99 +1,232 @@
-use std::collections::HashMap;
+use crate::storage::{HashStorage, Storage};
+use anyhow::{bail, Context};
+use edit::Totals;
+use std::{
+    fmt,
+    ops::{Add, Range, RangeInclusive, Sub},
+    str::FromStr,
+};
+
+mod edit;
 
 pub type EntityId = u32;
 
 #[derive(Debug)]
-pub struct Schematic {
-    entities: HashMap<EntityId, Entity>,
-    position_to_entity: HashMap<Position, EntityId>,
+pub struct Schematic<S = HashStorage> {
+    storage: S,
+    /// Every row padded to `cols` cells, kept so edits can re-read their neighbourhood.
+    cells: Grid,
+    cols: usize,
+    last_id: EntityId,
+    totals: Option<Totals>,
+    wrap: Wrap,
 }
 
-impl Schematic {
-    pub fn get_entities(&self) -> &HashMap<EntityId, Entity> {
-        &self.entities
+impl<S: Storage> Schematic<S> {
+    /// The number of rows and the length of the longest row.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.cells.len(), self.cols)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.storage.entities()
+    }
+
+    /// Every entity with its first cell, in row-major order whatever the storage.
+    pub fn entities_by_position(&self) -> Vec<(Position, &Entity)> {
+        let mut entities: Vec<_> = self
+            .entities()
+            .filter_map(|entity| Some((self.get_entity_positions(&entity.id).next()?, entity)))
+            .collect();
+        entities.sort_by_key(|(pos, _)| *pos);
+        entities
+    }
+
+    /// The positions `rule` considers adjacent to `pos`, taken around the edges that wrap.
+    pub fn neighbours(&self, pos: &Position, rule: Adjacency) -> impl Iterator<Item = Position> {
+        let (rows, cols) = self.dimensions();
+        let wrap = self.wrap;
+        let wrap_value = move |value: i32, wraps: bool, len: usize| match wraps && len > 0 {
+            true => value.rem_euclid(len as i32),
+            false => value,
+        };
+        let span = |value: i32, wraps: bool, len: usize| match wraps {
+            true => wrapped_reach(value, rule.radius(), len),
+            false => reach(value, rule.radius(), Some(len)),
+        };
+        pos.neighbours_in(
+            rule,
+            span(pos.row, wrap.rows, rows),
+            span(pos.col, wrap.cols, cols),
+        )
+        .map(move |neighbour| {
+            Position::new(
+                wrap_value(neighbour.row, wrap.rows, rows),
+                wrap_value(neighbour.col, wrap.cols, cols),
+            )
+        })
+    }
+
+    pub fn get_entity(&self, entity_id: &EntityId) -> Option<&Entity> {
+        self.storage.entity(*entity_id)
     }
 
     pub fn get_entity_at_position(&self, pos: &Position) -> Option<&Entity> {
-        self.position_to_entity
-            .get(pos)
-            .and_then(|id| self.entities.get(id))
+        self.storage
+            .entity_id_at(pos)
+            .and_then(|id| self.storage.entity(id))
     }
 
     pub fn get_entity_positions<'a>(
         &'a self,
         entity_id: &'a EntityId,
     ) -> impl Iterator<Item = Position> + 'a {
-        self.position_to_entity
-            .iter()
-            .filter(move |(_, id)| *id == entity_id)
-            .map(|(pos, _)| *pos)
+        self.storage.positions(*entity_id).iter().copied()
+    }
+
+    fn add_entity(&mut self, value: EntityValue, positions: Vec<Position>) -> EntityId {
+        self.last_id += 1;
+        let entity = Entity {
+            id: self.last_id,
+            value,
+        };
+        self.storage.insert(entity, positions);
+        self.last_id
     }
 }
 
-impl From<Grid> for Schematic {
+impl<S: Storage> From<Grid> for Schematic<S> {
     fn from(grid: Grid) -> Self {
-        let mut current_id = 0;
-        let mut next_id = || {
-            current_id += 1;
-            current_id
+        Self::with_wrap(grid, Wrap::default())
+    }
+}
+
+impl<S: Storage> Schematic<S> {
+    /// Builds a schematic whose edges wrap around as `wrap` says. When columns wrap, an entity
+    /// can run off the end of a row and continue at its start.
+    pub fn with_wrap(mut grid: Grid, wrap: Wrap) -> Self {
+        let cols = grid.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut grid {
+            row.resize(cols, Cell::Empty('.'));
+        }
+        let mut schematic = Schematic {
+            storage: S::new(grid.len(), cols),
+            cells: Vec::new(),
+            cols,
+            last_id: 0,
+            totals: None,
+            wrap,
         };
-        let mut entities = HashMap::new();
-        let mut position_to_entity = HashMap::new();
-
-        for r in 0..grid.len() {
-            let mut num_str = String::new();
-            for c in 0..grid[r].len() {
-                match &grid[r][c] {
-                    Cell::Digit(d) => {
-                        num_str.push(*d);
-                    }
-                    _ => {
-                        if !num_str.is_empty() {
-                            let number = num_str.parse::<u64>().unwrap();
-                            let entity = Entity {
-                                id: next_id(),
-                                value: EntityValue::Number(number),
-                            };
-                            for num_c in c - num_str.len()..c {
-                                position_to_entity
-                                    .insert((r as i32, num_c as i32).into(), entity.id);
-                            }
-                            entities.insert(entity.id, entity);
-                            num_str = String::new();
-                        }
-                    }
-                }
-                match &grid[r][c] {
-                    Cell::Symbol(s) => {
-                        let entity = Entity {
-                            id: next_id(),
-                            value: EntityValue::Symbol(*s),
-                        };
-                        position_to_entity.insert((r as i32, c as i32).into(), entity.id);
-                        entities.insert(entity.id, entity);
-                    }
-                    _ => {}
-                }
-            }
-            if !num_str.is_empty() {
-                let number = num_str.parse::<u64>().unwrap();
-                let entity = Entity {
-                    id: next_id(),
-                    value: EntityValue::Number(number),
-                };
-                let c = grid[r].len();
-                for num_c in c - num_str.len()..c {
-                    position_to_entity.insert((r as i32, num_c as i32).into(), entity.id);
-                }
-                entities.insert(entity.id, entity);
+        for (r, row) in grid.iter().enumerate() {
+            for (value, positions) in schematic.row_tokens(r, row) {
+                schematic.add_entity(value, positions);
             }
         }
+        schematic.cells = grid;
+        schematic
+    }
+
+    /// The entities of row `r` and the cells they cover, in reading order.
+    fn row_tokens(&self, r: usize, row: &[Cell]) -> Vec<(EntityValue, Vec<Position>)> {
+        // Nothing continues into an empty cell, a symbol or a sign, so reading a wrapping row
+        // from the first of those splits it the same way at every column. A row without any
+        // reads from its first column.
+        let offset = match self.wrap.cols {
+            true => row
+                .iter()
+                .position(|cell| matches!(cell, Cell::Empty(_) | Cell::Symbol(_) | Cell::Sign(_)))
+                .unwrap_or(0),
+            false => 0,
+        };
+        let rotated: Vec<Cell> = row[offset..]
+            .iter()
+            .chain(&row[..offset])
+            .copied()
+            .collect();
+        tokenize(&rotated)
+            .into_iter()
+            .map(|(value, span)| {
+                let positions = span
+                    .map(|c| (r as i32, ((c + offset) % row.len()) as i32).into())
+                    .collect();
+                (value, positions)
+            })
+            .collect()
+    }
+}
+
+/// Splits a row into entities and the columns they cover.
+pub fn tokenize(row: &[Cell]) -> Vec<(EntityValue, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut c = 0;
+    while c < row.len() {
+        let (value, end) = token_at(row, c);
+        if let Some(value) = value {
+            tokens.push((value, c..end));
+        }
+        c = end;
+    }
+    tokens
+}
+
+/// The entity starting at `start`, if the cell is not empty, and where it ends. A sign that is
+/// not followed by a digit is a symbol.
+fn token_at(row: &[Cell], start: usize) -> (Option<EntityValue>, usize) {
+    let mut c = start + 1;
+    let continues: fn(&Cell) -> bool = match row[start] {
+        Cell::Empty(_) => return (None, c),
+        Cell::Symbol(s) => return (Some(EntityValue::Symbol(s)), c),
+        Cell::Sign(s) if !matches!(row.get(c), Some(Cell::Digit(_))) => {
+            return (Some(EntityValue::Symbol(s)), c)
+        }
+        Cell::Sign(_) | Cell::Digit(_) => |cell| matches!(cell, Cell::Digit(_)),
+        Cell::Letter(_) => |cell| matches!(cell, Cell::Letter(_) | Cell::Digit(_)),
+    };
+    while row.get(c).is_some_and(continues) {
+        c += 1;
+    }
+    let value = match row[start] {
+        Cell::Letter(_) => {
+            EntityValue::Identifier(row[start..c].iter().filter_map(Cell::text).collect())
+        }
+        _ => EntityValue::Number(number(&row[start..c])),
+    };
+    (Some(value), c)
+}
+
+/// The most digits a number can have and still fit in an `i128`.
+pub const MAX_DIGITS: usize = 38;
+
+/// The value of a run of digits with an optional sign. Runs longer than `MAX_DIGITS` are
+/// rejected where input is read, so the saturation only guards against overflow panics.
+fn number(cells: &[Cell]) -> i128 {
+    let magnitude = cells.iter().fold(0i128, |number, cell| match cell {
+        Cell::Digit(d) => number
+            .saturating_mul(10)
+            .saturating_add(d.to_digit(10).unwrap_or(0).into()),
+        _ => number,
+    });
+    match cells.first() {
+        Some(Cell::Sign(_)) => -magnitude,
+        _ => magnitude,
+    }
+}
 
-        Schematic {
-            entities,
-            position_to_entity,
+/// Rejects a row with a number too long for an `i128`, counting a run of digits that continues
+/// across the edge when the row wraps.
+pub fn check_numbers(row: &[Cell], wraps: bool) -> anyhow::Result<()> {
+    let twice: &[Cell] = if wraps { row } else { &[] };
+    let mut run = 0;
+    for cell in row.iter().chain(twice) {
+        run = match cell {
+            Cell::Digit(_) => run + 1,
+            _ => 0,
+        };
+        if run.min(row.len()) > MAX_DIGITS {
+            bail!("numbers can have at most {MAX_DIGITS} digits");
         }
     }
+    Ok(())
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -103,43 +236,330 @@ pub struct Entity {
 }
 
 impl Entity {
-    pub fn is_number(&self) -> bool {
-        matches!(self.value, EntityValue::Number(_))
-    }
     pub fn is_symbol(&self) -> bool {
         matches!(self.value, EntityValue::Symbol(_))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EntityValue {
-    Number(u64),
+    Number(i128),
     Symbol(char),
+    /// A letter followed by any letters and digits, like `R2D2`. Never a symbol or a part.
+    Identifier(String),
 }
 
-#[derive(Debug)]
+impl fmt::Display for EntityValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityValue::Number(number) => write!(f, "{number}"),
+            EntityValue::Symbol(symbol) => write!(f, "{symbol}"),
+            EntityValue::Identifier(identifier) => write!(f, "{identifier}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cell {
     Digit(char),
+    /// A `-` that starts a negative number, or a symbol when no digit follows.
+    Sign(char),
+    Letter(char),
     Symbol(char),
-    Empty,
+    /// A `.`, or any character the rules do not read as anything else.
+    Empty(char),
+}
+
+impl Cell {
+    fn text(&self) -> Option<char> {
+        match self {
+            Cell::Digit(c) | Cell::Sign(c) | Cell::Letter(c) => Some(*c),
+            Cell::Symbol(_) | Cell::Empty(_) => None,
+        }
+    }
+
+    /// The character the cell was read from.
+    pub fn character(&self) -> char {
+        match *self {
+            Cell::Digit(c) | Cell::Sign(c) | Cell::Letter(c) | Cell::Symbol(c) | Cell::Empty(c) => {
+                c
+            }
+        }
+    }
+}
+
+/// How the characters of an input line become cells.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellRules {
+    /// The characters that are symbols. `None` means every character that is not `.`, a
+    /// digit or a letter; characters outside the set are read as empty cells.
+    pub symbols: Option<Vec<char>>,
+    /// Whether a `-` right before a digit, and not right after a letter or digit, is a sign.
+    pub signed: bool,
+}
+
+impl CellRules {
+    pub fn row(&self, line: &str) -> Vec<Cell> {
+        let chars: Vec<char> = line.chars().collect();
+        let alphanumeric = |i: Option<usize>| {
+            i.and_then(|i| chars.get(i))
+                .is_some_and(|c| c.is_alphanumeric())
+        };
+        chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| {
+                let sign = self.signed
+                    && chars.get(i + 1).is_some_and(char::is_ascii_digit)
+                    && !alphanumeric(i.checked_sub(1));
+                self.classify(c, sign)
+            })
+            .collect()
+    }
+
+    fn classify(&self, c: char, sign: bool) -> Cell {
+        match c {
+            '.' => Cell::Empty(c),
+            c if c.is_ascii_digit() => Cell::Digit(c),
+            c if c.is_alphabetic() => Cell::Letter(c),
+            '-' if sign => Cell::Sign(c),
+            c if self.is_symbol(c) => Cell::Symbol(c),
+            _ => Cell::Empty(c),
+        }
+    }
+
+    fn is_symbol(&self, c: char) -> bool {
+        match &self.symbols {
+            Some(symbols) => symbols.contains(&c),
+            None => !c.is_alphanumeric() && c != '.',
+        }
+    }
 }
 
 pub type Grid = Vec<Vec<Cell>>;
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// A cell by its row and column. Positions order row by row, and display as `row,col`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub struct Position {
     row: i32,
     col: i32,
 }
 
 impl Position {
-    pub fn adjacent(&self) -> Vec<Position> {
-        let deltas = [-1, 0, 1];
-        let rows = deltas.iter().map(|d| self.row + d);
-        let cols = || deltas.iter().map(|d| self.col + d);
-        rows.flat_map(move |row| cols().map(move |col| (row, col).into()))
-            .filter(|pos| pos != self)
-            .collect()
+    pub fn new(row: i32, col: i32) -> Self {
+        Self { row, col }
+    }
+
+    pub fn row(&self) -> i32 {
+        self.row
+    }
+
+    pub fn col(&self) -> i32 {
+        self.col
+    }
+
+    /// The positions `rule` considers adjacent, in row-major order, leaving out the rows and
+    /// columns outside `0..len` on the axes given a length.
+    pub fn neighbours(
+        &self,
+        rule: Adjacency,
+        (rows, cols): (Option<usize>, Option<usize>),
+    ) -> impl Iterator<Item = Position> {
+        self.neighbours_in(
+            rule,
+            reach(self.row, rule.radius(), rows),
+            reach(self.col, rule.radius(), cols),
+        )
+    }
+
+    /// The neighbours whose row and column fall in `rows` and `cols`.
+    fn neighbours_in(
+        self,
+        rule: Adjacency,
+        rows: RangeInclusive<i32>,
+        cols: RangeInclusive<i32>,
+    ) -> impl Iterator<Item = Position> {
+        rows.flat_map(move |row| cols.clone().map(move |col| Position::new(row, col)))
+            .filter(move |neighbour| rule.touches(&self, neighbour))
+    }
+
+    /// The neighbours inside a grid of `rows` rows and `cols` columns, in row-major order,
+    /// without visiting the cells outside it.
+    pub fn neighbours_within(
+        &self,
+        rule: Adjacency,
+        (rows, cols): (usize, usize),
+    ) -> impl Iterator<Item = Position> {
+        self.neighbours(rule, (Some(rows), Some(cols)))
+    }
+
+    pub fn is_within(&self, (rows, cols): (usize, usize)) -> bool {
+        let inside = |value: i32, len: usize| usize::try_from(value).is_ok_and(|value| value < len);
+        inside(self.row, rows) && inside(self.col, cols)
+    }
+
+    /// The number of steps between two cells moving only up, down, left or right.
+    pub fn manhattan_distance(&self, other: &Position) -> u32 {
+        self.row.abs_diff(other.row) + self.col.abs_diff(other.col)
+    }
+
+    /// The number of steps between two cells when diagonal moves are allowed.
+    pub fn chebyshev_distance(&self, other: &Position) -> u32 {
+        self.row
+            .abs_diff(other.row)
+            .max(self.col.abs_diff(other.col))
+    }
+}
+
+/// The values within `radius` of `value`, kept inside `0..len` when there is a length.
+fn reach(value: i32, radius: u32, len: Option<usize>) -> RangeInclusive<i32> {
+    let radius = i32::try_from(radius).unwrap_or(i32::MAX);
+    let (low, high) = (value.saturating_sub(radius), value.saturating_add(radius));
+    match len {
+        Some(len) => low.max(0)..=high.min(i32::try_from(len).unwrap_or(i32::MAX) - 1),
+        None => low..=high,
+    }
+}
+
+/// The values within `radius` of `value` on an axis of `len` values that wraps, never more
+/// than `len` of them so that each one wraps to a different value.
+fn wrapped_reach(value: i32, radius: u32, len: usize) -> RangeInclusive<i32> {
+    let len = i32::try_from(len).unwrap_or(i32::MAX);
+    if len == 0 {
+        return reach(value, radius, Some(0));
+    }
+    let radius = i32::try_from(radius).unwrap_or(i32::MAX);
+    let (below, above) = (radius.min((len - 1) / 2), radius.min(len / 2));
+    value.saturating_sub(below)..=value.saturating_add(above)
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.row, self.col)
+    }
+}
+
+/// A step between two positions, in rows and columns.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Direction {
+    pub d_row: i32,
+    pub d_col: i32,
+}
+
+impl Direction {
+    pub const fn new(d_row: i32, d_col: i32) -> Self {
+        Self { d_row, d_col }
+    }
+}
+
+impl Add<Direction> for Position {
+    type Output = Position;
+
+    fn add(self, direction: Direction) -> Position {
+        Position::new(self.row + direction.d_row, self.col + direction.d_col)
+    }
+}
+
+impl Sub<Direction> for Position {
+    type Output = Position;
+
+    fn sub(self, direction: Direction) -> Position {
+        Position::new(self.row - direction.d_row, self.col - direction.d_col)
+    }
+}
+
+impl Sub for Position {
+    type Output = Direction;
+
+    /// The direction that leads from `other` to `self`.
+    fn sub(self, other: Position) -> Direction {
+        Direction::new(self.row - other.row, self.col - other.col)
+    }
+}
+
+/// Which cells around a position count as touching it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Adjacency {
+    /// The 8 cells sharing an edge or a corner.
+    #[default]
+    Eight,
+    /// The 4 cells sharing an edge.
+    Four,
+    /// Every cell within `k` rows and `k` columns.
+    Chebyshev(u32),
+    /// The cells directly left and right.
+    SameRow,
+    /// The 4 cells sharing only a corner.
+    Diagonal,
+}
+
+impl Adjacency {
+    /// How many rows and columns away an adjacent cell can be.
+    pub fn radius(self) -> u32 {
+        match self {
+            Adjacency::Chebyshev(k) => k,
+            _ => 1,
+        }
+    }
+
+    /// Whether the rule makes two different positions adjacent.
+    pub fn touches(self, a: &Position, b: &Position) -> bool {
+        let distance = a.chebyshev_distance(b);
+        let step = *b - *a;
+        distance > 0
+            && distance <= self.radius()
+            && match self {
+                Adjacency::Eight | Adjacency::Chebyshev(_) => true,
+                Adjacency::Four => a.manhattan_distance(b) == 1,
+                Adjacency::SameRow => step.d_row == 0,
+                Adjacency::Diagonal => step.d_row != 0 && step.d_col != 0,
+            }
+    }
+}
+
+impl FromStr for Adjacency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Self::Eight),
+            "4" => Ok(Self::Four),
+            "row" => Ok(Self::SameRow),
+            "diagonal" => Ok(Self::Diagonal),
+            _ => match s.strip_prefix("chebyshev:") {
+                Some(k) => {
+                    let k: u16 = k.parse().with_context(|| {
+                        format!("invalid chebyshev radius {k:?}, expected 0 to {}", u16::MAX)
+                    })?;
+                    Ok(Self::Chebyshev(k.into()))
+                }
+                None => bail!("unknown adjacency {s}, expected 8, 4, chebyshev:K, row or diagonal"),
+            },
+        }
+    }
+}
+
+/// Which edges of the grid touch the opposite edge.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Wrap {
+    /// The last row touches the first.
+    pub rows: bool,
+    /// The last column touches the first, and numbers can continue across.
+    pub cols: bool,
+}
+
+impl FromStr for Wrap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rows, cols) = match s {
+            "none" => (false, false),
+            "rows" => (true, false),
+            "cols" => (false, true),
+            "both" => (true, true),
+            _ => bail!("unknown wrap {s}, expected none, rows, cols or both"),
+        };
+        Ok(Self { rows, cols })
     }
 }
 
@@ -159,8 +579,9 @@ mod tests {
     #[test]
     fn test_position_adjacent() {
         let p = Position { row: 0, col: 0 };
+        let neighbours = |rule| p.neighbours(rule, (None, None)).collect::<Vec<_>>();
         assert_eq!(
-            p.adjacent(),
+            neighbours(Adjacency::Eight),
             vec![
                 Position { row: -1, col: -1 },
                 Position { row: -1, col: 0 },
@@ -172,5 +593,141 @@ mod tests {
                 Position { row: 1, col: 1 },
             ]
         );
+        assert_eq!(
+            neighbours(Adjacency::Chebyshev(1)),
+            neighbours(Adjacency::Eight)
+        );
+        assert_eq!(
+            neighbours(Adjacency::Four),
+            vec![
+                Position { row: -1, col: 0 },
+                Position { row: 0, col: -1 },
+                Position { row: 0, col: 1 },
+                Position { row: 1, col: 0 },
+            ]
+        );
+        assert_eq!(
+            neighbours(Adjacency::SameRow),
+            vec![Position { row: 0, col: -1 }, Position { row: 0, col: 1 }]
+        );
+        assert_eq!(
+            neighbours(Adjacency::Diagonal),
+            vec![
+                Position { row: -1, col: -1 },
+                Position { row: -1, col: 1 },
+                Position { row: 1, col: -1 },
+                Position { row: 1, col: 1 },
+            ]
+        );
+        assert_eq!(neighbours(Adjacency::Chebyshev(0)), []);
+        let far: Vec<_> = Position { row: 5, col: -3 }
+            .neighbours(Adjacency::Chebyshev(2), (None, None))
+            .collect();
+        assert_eq!(far.len(), 24);
+        assert_eq!(far[0], Position { row: 3, col: -5 });
+        assert_eq!(far[23], Position { row: 7, col: -1 });
+        assert!(!far.contains(&Position { row: 5, col: -3 }));
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let rules = CellRules {
+            signed: true,
+            ..CellRules::default()
+        };
+        let values = |line: &str| -> Vec<EntityValue> {
+            tokenize(&rules.row(line))
+                .into_iter()
+                .map(|(value, _)| value)
+                .collect()
+        };
+        assert_eq!(
+            values("12345678901234567890*"),
+            [
+                EntityValue::Number(12345678901234567890),
+                EntityValue::Symbol('*')
+            ]
+        );
+        let longest = "9".repeat(MAX_DIGITS);
+        assert_eq!(
+            values(&format!("-{longest}")),
+            [EntityValue::Number(-longest.parse::<i128>().unwrap())]
+        );
+
+        assert!(check_numbers(&rules.row(&longest), false).is_ok());
+        assert!(check_numbers(&rules.row(&format!("{longest}1")), false).is_err());
+        // 20 digits at the end continue into 19 at the start when the row wraps.
+        let split = format!("{}.{}", "1".repeat(19), "2".repeat(20));
+        assert!(check_numbers(&rules.row(&split), false).is_ok());
+        assert!(check_numbers(&rules.row(&split), true).is_err());
+    }
+
+    #[test]
+    fn test_parse_adjacency() {
+        assert_eq!("8".parse::<Adjacency>().unwrap(), Adjacency::Eight);
+        assert_eq!("4".parse::<Adjacency>().unwrap(), Adjacency::Four);
+        assert_eq!(
+            "chebyshev:3".parse::<Adjacency>().unwrap(),
+            Adjacency::Chebyshev(3)
+        );
+        assert_eq!("row".parse::<Adjacency>().unwrap(), Adjacency::SameRow);
+        assert_eq!(
+            "diagonal".parse::<Adjacency>().unwrap(),
+            Adjacency::Diagonal
+        );
+        assert!("chebyshev:x".parse::<Adjacency>().is_err());
+        assert!("chebyshev:4294967295".parse::<Adjacency>().is_err());
+        assert!("6".parse::<Adjacency>().is_err());
+    }
+
+    #[test]
+    fn test_position_arithmetic() {
+        let p = Position::new(2, 5);
+        assert_eq!((p.row(), p.col()), (2, 5));
+        assert_eq!(p.to_string(), "2,5");
+        assert_eq!(p + Direction::new(-1, 2), Position::new(1, 7));
+        assert_eq!(p - Direction::new(-1, 2), Position::new(3, 3));
+        assert_eq!(Position::new(1, 7) - p, Direction::new(-1, 2));
+
+        let q = Position::new(-1, 1);
+        assert_eq!(p.manhattan_distance(&q), 7);
+        assert_eq!(p.chebyshev_distance(&q), 4);
+        assert_eq!(q.manhattan_distance(&q), 0);
+
+        let mut positions = vec![
+            Position::new(1, 0),
+            Position::new(0, 3),
+            Position::new(0, -1),
+        ];
+        positions.sort();
+        assert_eq!(
+            positions,
+            [
+                Position::new(0, -1),
+                Position::new(0, 3),
+                Position::new(1, 0)
+            ]
+        );
+
+        let corner = Position::new(0, 0);
+        assert_eq!(
+            corner
+                .neighbours_within(Adjacency::Eight, (3, 3))
+                .collect::<Vec<_>>(),
+            [
+                Position::new(0, 1),
+                Position::new(1, 0),
+                Position::new(1, 1)
+            ]
+        );
+        assert_eq!(
+            Position::new(2, 1)
+                .neighbours_within(Adjacency::Four, (3, 2))
+                .collect::<Vec<_>>(),
+            [Position::new(1, 1), Position::new(2, 0)]
+        );
+        assert!(!Position::new(0, 2).is_within((3, 2)));
+        assert!(Adjacency::Four.touches(&corner, &Position::new(0, 1)));
+        assert!(!Adjacency::Four.touches(&corner, &corner));
     }
 }
@@ -0,0 +1,169 @@
+use super::{
+    check_numbers, token_at, Adjacency, Cell, CellRules, EntityId, EntityValue, Position, Schematic,
+};
+use crate::{gear::GearRule, is_part_number, storage::Storage};
+use std::collections::HashSet;
+
+type Tokens = Vec<(EntityValue, Vec<Position>)>;
+
+/// The part number and gear ratio sums under one adjacency and gear rule, kept up to date
+/// by `Schematic::set_cell`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Totals {
+    adjacency: Adjacency,
+    gear_rule: GearRule,
+    pub part_number_sum: i128,
+    pub gear_ratio_sum: i128,
+}
+
+impl<S: Storage> Schematic<S> {
+    /// Computes the sums once so that later edits only adjust them.
+    pub fn track_totals(&mut self, adjacency: Adjacency, gear_rule: GearRule) {
+        let mut totals = Totals {
+            adjacency,
+            gear_rule,
+            part_number_sum: 0,
+            gear_ratio_sum: 0,
+        };
+        let ids: Vec<EntityId> = self.entities().map(|entity| entity.id).collect();
+        self.add_contributions(&mut totals, &ids, 1);
+        self.totals = Some(totals);
+    }
+
+    pub fn totals(&self) -> Option<&Totals> {
+        self.totals.as_ref()
+    }
+
+    pub fn get_cell(&self, pos: &Position) -> Option<Cell> {
+        pos.is_within(self.dimensions())
+            .then(|| self.cells[pos.row() as usize][pos.col() as usize])
+    }
+
+    /// Replaces one cell, re-reading only the entities on its row that the change can merge,
+    /// split or retype, and adjusting the tracked totals around them.
+    ///
+    /// Panics if `pos` is outside the schematic's dimensions.
+    pub fn set_cell(&mut self, pos: Position, cell: Cell) {
+        assert!(self.get_cell(&pos).is_some(), "{pos:?} is outside the grid");
+        let (row, col) = (pos.row() as usize, pos.col() as usize);
+        let at = |col: usize| Position::new(pos.row(), col as i32);
+
+        let (region, tokens) = if self.wrap.cols {
+            // A wrapping row may have to be split from a different column, so re-read all of it.
+            self.cells[row][col] = cell;
+            let tokens = self.row_tokens(row, &self.cells[row]);
+            ((0..self.cols).map(at).collect(), tokens)
+        } else {
+            self.reread(row, col, cell)
+        };
+
+        // Only entities in the re-read cells or touching them can change their contribution.
+        let mut totals = self.totals.take();
+        if let Some(totals) = &mut totals {
+            let affected = self.ids_near(&region, totals.adjacency);
+            self.add_contributions(totals, &affected, -1);
+        }
+        let old: HashSet<EntityId> = region
+            .iter()
+            .filter_map(|pos| self.storage.entity_id_at(pos))
+            .collect();
+        for id in old {
+            self.storage.remove(id);
+        }
+        for (value, positions) in tokens {
+            self.add_entity(value, positions);
+        }
+        if let Some(totals) = &mut totals {
+            let affected = self.ids_near(&region, totals.adjacency);
+            self.add_contributions(totals, &affected, 1);
+        }
+        self.totals = totals;
+    }
+
+    /// Writes `c` at `pos` and re-reads its row with `rules` the way parsing the edited text
+    /// would, since whether a `-` is a sign depends on the characters around it. Fails, leaving
+    /// the schematic as it was, if the edit makes a number too long.
+    ///
+    /// Panics if `pos` is outside the schematic's dimensions.
+    pub fn set_char(&mut self, pos: Position, c: char, rules: &CellRules) -> anyhow::Result<()> {
+        assert!(self.get_cell(&pos).is_some(), "{pos:?} is outside the grid");
+        let row = pos.row() as usize;
+        let mut line: Vec<char> = self.cells[row].iter().map(Cell::character).collect();
+        line[pos.col() as usize] = c;
+        let cells = rules.row(&line.into_iter().collect::<String>());
+        check_numbers(&cells, self.wrap.cols)?;
+        for (col, cell) in (0..).zip(cells) {
+            let at = Position::new(pos.row(), col);
+            if self.get_cell(&at) != Some(cell) {
+                self.set_cell(at, cell);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets a cell on a row that does not wrap and reads the entities it can merge, split or
+    /// retype, returning the cells they cover.
+    fn reread(&mut self, row: usize, col: usize, cell: Cell) -> (Vec<Position>, Tokens) {
+        let at = |col: usize| Position::new(row as i32, col as i32);
+
+        // Tokens start where they did before up to the entity left of the edit, so re-read
+        // from there until a token starts where an old one did, or on an empty cell.
+        let start = match col
+            .checked_sub(1)
+            .and_then(|c| self.storage.entity_id_at(&at(c)))
+        {
+            Some(id) => self.storage.positions(id)[0].col() as usize,
+            None => col,
+        };
+        self.cells[row][col] = cell;
+        let mut tokens = Vec::new();
+        let mut end = start;
+        while end < self.cols && !(end > col && self.is_token_start(at(end))) {
+            let (value, next) = token_at(&self.cells[row], end);
+            if let Some(value) = value {
+                tokens.push((value, (end..next).map(at).collect()));
+            }
+            end = next;
+        }
+        ((start..end).map(at).collect(), tokens)
+    }
+
+    /// Whether an entity starts at `pos` or it is empty, before the row is re-read.
+    fn is_token_start(&self, pos: Position) -> bool {
+        match self.storage.entity_id_at(&pos) {
+            Some(id) => self.storage.positions(id)[0] == pos,
+            None => true,
+        }
+    }
+
+    /// The distinct entities covering `region` or a cell next to it.
+    fn ids_near(&self, region: &[Position], adjacency: Adjacency) -> Vec<EntityId> {
+        let mut seen = HashSet::new();
+        region
+            .iter()
+            .flat_map(|pos| std::iter::once(*pos).chain(self.neighbours(pos, adjacency)))
+            .filter_map(|pos| self.storage.entity_id_at(&pos))
+            .filter(|id| seen.insert(*id))
+            .collect()
+    }
+
+    fn add_contributions(&self, totals: &mut Totals, ids: &[EntityId], sign: i128) {
+        for id in ids {
+            let Some(entity) = self.get_entity(id) else {
+                continue;
+            };
+            match entity.value {
+                EntityValue::Number(num) if is_part_number(self, id, totals.adjacency) => {
+                    totals.part_number_sum += sign * num;
+                }
+                EntityValue::Symbol(_) => {
+                    let gear = totals.gear_rule.numbers(self, entity, totals.adjacency);
+                    if let Some(nums) = gear {
+                        totals.gear_ratio_sum += sign * totals.gear_rule.ratio(&nums);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
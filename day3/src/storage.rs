@@ -0,0 +1,159 @@
+use crate::schematic::{Entity, EntityId, Position};
+use anyhow::bail;
+use std::{collections::HashMap, str::FromStr};
+
+/// Where a `Schematic` keeps its entities and the cells they cover.
+pub trait Storage {
+    /// Empty storage for a grid of `rows` rows of at most `cols` cells.
+    fn new(rows: usize, cols: usize) -> Self;
+    /// Entities are inserted with ids counting up from 1.
+    fn insert(&mut self, entity: Entity, positions: Vec<Position>);
+    /// Removes an entity and frees its cells. Its id is not reused.
+    fn remove(&mut self, id: EntityId) -> Option<Entity>;
+    fn entity(&self, id: EntityId) -> Option<&Entity>;
+    fn entity_id_at(&self, pos: &Position) -> Option<EntityId>;
+    fn entities(&self) -> impl Iterator<Item = &Entity>;
+    fn positions(&self, id: EntityId) -> &[Position];
+}
+
+/// Keys everything by position and id, so only occupied cells take space.
+#[derive(Debug, Default)]
+pub struct HashStorage {
+    entities: HashMap<EntityId, Entity>,
+    position_to_entity: HashMap<Position, EntityId>,
+    entity_positions: HashMap<EntityId, Vec<Position>>,
+}
+
+impl Storage for HashStorage {
+    fn new(_rows: usize, _cols: usize) -> Self {
+        Self::default()
+    }
+
+    fn insert(&mut self, entity: Entity, positions: Vec<Position>) {
+        for pos in &positions {
+            self.position_to_entity.insert(*pos, entity.id);
+        }
+        self.entity_positions.insert(entity.id, positions);
+        self.entities.insert(entity.id, entity);
+    }
+
+    fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        for pos in self.entity_positions.remove(&id).unwrap_or_default() {
+            self.position_to_entity.remove(&pos);
+        }
+        self.entities.remove(&id)
+    }
+
+    fn entity(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    fn entity_id_at(&self, pos: &Position) -> Option<EntityId> {
+        self.position_to_entity.get(pos).copied()
+    }
+
+    fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    fn positions(&self, id: EntityId) -> &[Position] {
+        self.entity_positions.get(&id).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A row-major cell array and entities indexed by id, for grids that are mostly full.
+#[derive(Debug, Default)]
+pub struct DenseStorage {
+    cols: usize,
+    cells: Vec<Option<EntityId>>,
+    /// Removed entities leave a `None` so later ids keep their index.
+    entities: Vec<Option<Entity>>,
+    positions: Vec<Vec<Position>>,
+}
+
+impl DenseStorage {
+    fn cell_index(&self, pos: &Position) -> Option<usize> {
+        let (row, col) = (
+            usize::try_from(pos.row()).ok()?,
+            usize::try_from(pos.col()).ok()?,
+        );
+        let index = row * self.cols + col;
+        (col < self.cols && index < self.cells.len()).then_some(index)
+    }
+
+    fn entity_index(id: EntityId) -> Option<usize> {
+        (id as usize).checked_sub(1)
+    }
+}
+
+impl Storage for DenseStorage {
+    fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            cols,
+            cells: vec![None; rows * cols],
+            ..Self::default()
+        }
+    }
+
+    fn insert(&mut self, entity: Entity, positions: Vec<Position>) {
+        assert_eq!(
+            Self::entity_index(entity.id),
+            Some(self.entities.len()),
+            "entity ids must count up from 1"
+        );
+        for pos in &positions {
+            let index = self.cell_index(pos).expect("position outside the grid");
+            self.cells[index] = Some(entity.id);
+        }
+        self.positions.push(positions);
+        self.entities.push(Some(entity));
+    }
+
+    fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        let index = Self::entity_index(id)?;
+        let entity = self.entities.get_mut(index)?.take()?;
+        for pos in std::mem::take(&mut self.positions[index]) {
+            let cell = self.cell_index(&pos).expect("position outside the grid");
+            self.cells[cell] = None;
+        }
+        Some(entity)
+    }
+
+    fn entity(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(Self::entity_index(id)?)?.as_ref()
+    }
+
+    fn entity_id_at(&self, pos: &Position) -> Option<EntityId> {
+        self.cells[self.cell_index(pos)?]
+    }
+
+    fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.iter().flatten()
+    }
+
+    fn positions(&self, id: EntityId) -> &[Position] {
+        Self::entity_index(id)
+            .and_then(|index| self.positions.get(index))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Which storage to build a schematic with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Hash,
+    Dense,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(Self::Hash),
+            "dense" => Ok(Self::Dense),
+            _ => bail!("unknown storage {s}, expected hash or dense"),
+        }
+    }
+}
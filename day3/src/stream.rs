@@ -0,0 +1,122 @@
+use crate::{
+    gear::GearRule,
+    schematic::{check_numbers, tokenize, Adjacency, Cell, CellRules, EntityValue, Position},
+};
+use anyhow::Context;
+use std::{
+    collections::{HashMap, VecDeque},
+    io::BufRead,
+    ops::Range,
+};
+
+/// Both parts in one pass over `input`, holding only the rows within reach of the row being
+/// scored: three rows for the default adjacency, `2k + 1` for `chebyshev:K`.
+pub fn solve(
+    input: impl BufRead,
+    rules: &CellRules,
+    adjacency: Adjacency,
+    gear_rule: &GearRule,
+) -> anyhow::Result<(i128, i128)> {
+    let radius = adjacency.radius() as usize;
+    // Empty rows above the first line and below the last let every row be scored in the middle.
+    let mut window: VecDeque<Row> = (0..radius).map(|_| Row::default()).collect();
+    let mut totals = (0, 0);
+    let mut score = |window: &mut VecDeque<Row>| {
+        if window.len() == 2 * radius + 1 {
+            let (part_1, part_2) = score_middle(window, adjacency, gear_rule);
+            totals.0 += part_1;
+            totals.1 += part_2;
+            window.pop_front();
+        }
+    };
+    for (number, line) in (1..).zip(input.lines()) {
+        let row = rules.row(&line?);
+        check_numbers(&row, false).with_context(|| format!("line {number}"))?;
+        window.push_back(Row::new(&row));
+        score(&mut window);
+    }
+    for _ in 0..radius {
+        window.push_back(Row::default());
+        score(&mut window);
+    }
+    Ok(totals)
+}
+
+#[derive(Debug, Default)]
+struct Row {
+    entities: Vec<(EntityValue, Range<usize>)>,
+    /// The index into `entities` of the entity covering each column.
+    cells: Vec<Option<usize>>,
+}
+
+impl Row {
+    fn new(cells: &[Cell]) -> Self {
+        let entities = tokenize(cells);
+        let mut covered = vec![None; cells.len()];
+        for (index, (_, span)) in entities.iter().enumerate() {
+            for col in span.clone() {
+                covered[col] = Some(index);
+            }
+        }
+        Self {
+            entities,
+            cells: covered,
+        }
+    }
+
+    fn entity_at(&self, col: i32) -> Option<usize> {
+        *self.cells.get(usize::try_from(col).ok()?)?
+    }
+}
+
+/// The part numbers and gear ratios of the middle row of `window`.
+fn score_middle(
+    window: &VecDeque<Row>,
+    adjacency: Adjacency,
+    gear_rule: &GearRule,
+) -> (i128, i128) {
+    let middle = window.len() / 2;
+    let cols = window.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+    // Entities around `span` in the middle row, as (row in the window, index in that row).
+    let around = |span: &Range<usize>| {
+        span.clone()
+            .flat_map(|col| {
+                Position::from((middle as i32, col as i32))
+                    .neighbours_within(adjacency, (window.len(), cols))
+            })
+            .filter_map(|pos| {
+                let row = &window[usize::try_from(pos.row()).ok()?];
+                Some((pos.row() as usize, row.entity_at(pos.col())?))
+            })
+            .collect::<Vec<_>>()
+    };
+    let value = |(row, index): (usize, usize)| &window[row].entities[index].0;
+
+    let (mut part_1, mut part_2) = (0, 0);
+    for (entity, span) in &window[middle].entities {
+        match *entity {
+            EntityValue::Number(num) => {
+                let is_part = around(span)
+                    .into_iter()
+                    .any(|found| matches!(value(found), EntityValue::Symbol(_)));
+                if is_part {
+                    part_1 += num;
+                }
+            }
+            EntityValue::Symbol(symbol) if gear_rule.is_gear_symbol(symbol) => {
+                let nums: HashMap<_, _> = around(span)
+                    .into_iter()
+                    .filter_map(|found| match value(found) {
+                        EntityValue::Number(num) => Some((found, *num)),
+                        _ => None,
+                    })
+                    .collect();
+                if gear_rule.count.matches(nums.len()) {
+                    part_2 += gear_rule.aggregate.apply(nums.into_values());
+                }
+            }
+            _ => {}
+        }
+    }
+    (part_1, part_2)
+}
@@ -37,7 +37,7 @@ fn parse_input(input: impl BufRead) -> Input {
     let distances = lines.next().unwrap();
     times
         .into_iter()
-        .zip(distances.into_iter())
+        .zip(distances)
         .map(|(time, distance)| Race {
             race_duration: time,
             record_distance: distance,
@@ -64,7 +64,7 @@ fn ways_to_beat_record(race: &Race) -> usize {
 
 fn part_2(input: &Input) -> u64 {
     let input = input
-        .into_iter()
+        .iter()
         .cloned()
         .reduce(|acc, other| acc.concat(&other))
         .unwrap();
@@ -63,7 +63,7 @@ impl Hand {
     fn card_counts(&self) -> HashMap<CardVal, usize> {
         let mut counts = HashMap::new();
         for card in self.cards.iter() {
-            let entry = counts.entry(card.clone()).or_insert(0);
+            let entry = counts.entry(*card).or_insert(0);
             *entry += 1;
         }
         counts
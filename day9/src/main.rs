@@ -25,23 +25,23 @@ fn part_2(input: &Input) -> i64 {
 }
 
 fn get_next_value(report: &[i32]) -> i64 {
-    if report.len() == 0 {
+    if report.is_empty() {
         return 0;
     }
     if all_same(report) {
         return report[0] as i64;
     }
-    return report[report.len() - 1] as i64 + get_next_value(&differences(report));
+    report[report.len() - 1] as i64 + get_next_value(&differences(report))
 }
 
 fn get_prev_value(report: &[i32]) -> i64 {
-    if report.len() == 0 {
+    if report.is_empty() {
         return 0;
     }
     if all_same(report) {
         return report[0] as i64;
     }
-    return report[0] as i64 - get_prev_value(&differences(report));
+    report[0] as i64 - get_prev_value(&differences(report))
 }
 
 fn all_same(nums: &[i32]) -> bool {